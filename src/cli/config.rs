@@ -0,0 +1,431 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::constants::{Direction, Location, McVersion, MC_VERSIONS};
+use crate::image_processor::dithering::Dithering;
+use crate::image_processor::lookup_table::LookupTable;
+use crate::image_processor::metric::ColorMetric;
+use crate::image_processor::preprocess::Crop;
+use crate::image_processor::resize::{Anchor, Fit, ResizeFilter};
+use crate::output_generator::playback::{EndFrame, PlaybackMode};
+
+/// Every option that describes a conversion.
+/// The same struct is filled in from command line flags and from a project file (`mapmaker.toml`),
+/// so any option added here can be given either way. Missing values are `None`.
+#[derive(Args, Serialize, Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Coordinates of the top left corner, as `x,y,z`
+    #[arg(long, value_name = "X,Y,Z", value_parser = parse_location, allow_hyphen_values = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_left: Option<Location>,
+
+    /// Direction the maps will be facing
+    #[arg(long, value_enum)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>,
+
+    /// Direction the top of the image points to, for maps facing up or down
+    #[arg(long, value_enum)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top: Option<Direction>,
+
+    /// Index of the first map (the next free map ID if the world already has maps)
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starting_index: Option<usize>,
+
+    /// Minecraft world save to install into, instead of an output folder.
+    /// The starting index is read from the world
+    #[arg(short, long, conflicts_with_all = ["starting_index", "output_path"])]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub world: Option<PathBuf>,
+
+    /// Minecraft version to generate files for, e.g. `1.20.4`.
+    /// Detected from the world when installing into one
+    #[arg(long, value_name = "VERSION", value_parser = parse_mc_version)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mc_version: Option<String>,
+
+    /// Name of the display, which namespaces its functions, scores, tags and storage
+    /// so that several displays can be installed in one world [default: mapmaker]
+    #[arg(long, value_name = "NAME")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+
+    /// Crop every frame to a rectangle, as `x,y,width,height` in pixels or percentages, e.g. `0,12%,100%,76%`
+    #[arg(long, value_name = "X,Y,W,H", value_parser = parse_crop)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crop: Option<String>,
+
+    /// Rotate every frame clockwise by 90, 180 or 270 degrees, after cropping
+    #[arg(long, value_name = "DEGREES", value_parser = parse_rotation)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<u32>,
+
+    /// Mirror every frame left to right, after rotating
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", value_name = "BOOL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flip_horizontal: Option<bool>,
+
+    /// Mirror every frame top to bottom, after rotating
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", value_name = "BOOL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flip_vertical: Option<bool>,
+
+    /// Number of maps to fill, as `columns x rows`, e.g. `8x4`.
+    /// Defaults to the size of the image rounded up to whole maps
+    #[arg(long, value_name = "COLUMNSxROWS", value_parser = parse_map_grid, conflicts_with_all = ["width_maps", "height_maps"])]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maps: Option<(u32, u32)>,
+
+    /// Number of maps horizontally, with the rows following from the aspect ratio of the image
+    #[arg(long, value_name = "COLUMNS", conflicts_with = "height_maps")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width_maps: Option<u32>,
+
+    /// Number of maps vertically, with the columns following from the aspect ratio of the image
+    #[arg(long, value_name = "ROWS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height_maps: Option<u32>,
+
+    /// How the image is fitted onto the maps [default: contain if a number of maps is given, else none]
+    #[arg(long, value_enum)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fit: Option<Fit>,
+
+    /// Resampling filter used when scaling the image [default: lanczos3]
+    #[arg(long, value_enum)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<ResizeFilter>,
+
+    /// Where the image is placed on the maps when it does not fill them exactly [default: center]
+    #[arg(long, value_enum)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<Anchor>,
+
+    /// Added to the brightness of every channel, from -1 to 1 [default: 0]
+    #[arg(long, value_name = "AMOUNT", allow_hyphen_values = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<f32>,
+
+    /// Contrast multiplier, 1 is unchanged [default: 1]
+    #[arg(long, value_name = "FACTOR")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contrast: Option<f32>,
+
+    /// Saturation multiplier, 1 is unchanged and 0 is greyscale [default: 1]
+    #[arg(long, value_name = "FACTOR")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saturation: Option<f32>,
+
+    /// Gamma correction, values above 1 brighten the midtones [default: 1]
+    #[arg(long, value_name = "GAMMA")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gamma: Option<f32>,
+
+    /// Stretch the levels to the full range, measured over the whole sequence
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", value_name = "BOOL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_levels: Option<bool>,
+
+    /// Pixels with an alpha value below this are transparent [default: 128]
+    #[arg(long, value_name = "ALPHA")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha_threshold: Option<u8>,
+
+    /// Make the border around images that do not fit the maps exactly transparent instead of black
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", value_name = "BOOL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transparent_padding: Option<bool>,
+
+    /// Dithering algorithm [default: floyd-steinberg]
+    #[arg(long, value_enum)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dithering: Option<Dithering>,
+
+    /// Scan every other row right to left when dithering
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", value_name = "BOOL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serpentine: Option<bool>,
+
+    /// How color differences are measured when matching colors to the palette [default: srgb]
+    #[arg(long, value_enum)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_metric: Option<ColorMetric>,
+
+    /// Dither in linear light instead of gamma-encoded sRGB, for more accurate gradients
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", value_name = "BOOL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linear_light: Option<bool>,
+
+    /// Match colors through a precomputed table, cached between runs [default: none]
+    #[arg(long, value_enum)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lookup_table: Option<LookupTable>,
+
+    /// Folder containing the images to convert, a file name pattern like `frames/frame_%05d.png`,
+    /// a text file listing one frame per line, or an animated GIF, PNG or WebP file
+    #[arg(short, long = "input")]
+    #[serde(rename = "input", skip_serializing_if = "Option::is_none")]
+    pub input_path: Option<PathBuf>,
+
+    /// First number of a file name pattern [default: 0 or 1, whichever exists]
+    #[arg(long, value_name = "NUMBER")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_number: Option<usize>,
+
+    /// Last number of a file name pattern [default: the last one before a missing file]
+    #[arg(long, value_name = "NUMBER")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_number: Option<usize>,
+
+    /// Index of the first frame to keep, counting from 0 [default: 0]
+    #[arg(long, value_name = "INDEX")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_frame: Option<usize>,
+
+    /// Index of the last frame to keep [default: the last frame]
+    #[arg(long, value_name = "INDEX")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_frame: Option<usize>,
+
+    /// Keep only every Nth frame [default: 1]
+    #[arg(long, value_name = "N")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame_step: Option<usize>,
+
+    /// Frame rate of the input, which overrides the delays of an animated file
+    #[arg(long, value_name = "FPS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_fps: Option<f64>,
+
+    /// Frame rate to resample the input to, by dropping or repeating frames
+    #[arg(long, value_name = "FPS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_fps: Option<f64>,
+
+    /// Frame rate the datapack plays the animation at, at most 20 (one frame per tick)
+    /// [default: from the delays of the frames, or 20]
+    #[arg(long, value_name = "FPS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f64>,
+
+    /// How the animation plays through its frames [default: loop]
+    #[arg(long, value_enum)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub playback: Option<PlaybackMode>,
+
+    /// Number of times to play the animation before stopping, for loop and ping-pong playback [default: forever]
+    #[arg(long, value_name = "COUNT")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loop_count: Option<u32>,
+
+    /// Frame to stop on once the animation has finished playing [default: first for ping-pong, else last]
+    #[arg(long, value_enum)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_frame: Option<EndFrame>,
+
+    /// Let players control playback with `/trigger mapmaker_control` and `/trigger mapmaker_speed`,
+    /// without being operators
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", value_name = "BOOL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player_controls: Option<bool>,
+
+    /// Only let players within this many blocks of the display use the player controls,
+    /// which turns them on [default: any distance]
+    #[arg(long, value_name = "BLOCKS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub control_radius: Option<f64>,
+
+    /// Folder that will contain the data/ and datapacks/ folders
+    #[arg(short, long = "output")]
+    #[serde(rename = "output", skip_serializing_if = "Option::is_none")]
+    pub output_path: Option<PathBuf>,
+}
+
+impl Options {
+    /// Reads a project file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read project file {}", path.display()))?;
+        let options: Options = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse project file {}", path.display()))?;
+        options
+            .validate()
+            .with_context(|| format!("invalid project file {}", path.display()))?;
+        Ok(options)
+    }
+
+    /// Checks that the values are in range, and the values that clap only checks when they are given as flags,
+    /// so that options read from a project file are held to the same rules.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(mc_version) = &self.mc_version {
+            parse_mc_version(mc_version).map_err(|e| anyhow::anyhow!("mc_version: {e}"))?;
+        }
+        if self.top.is_some_and(Direction::is_vertical) {
+            anyhow::bail!("top: the top of the image must point in a horizontal direction");
+        }
+        if self.world.is_some() && (self.starting_index.is_some() || self.output_path.is_some()) {
+            anyhow::bail!("world cannot be combined with starting_index or output");
+        }
+        if let Some(crop) = &self.crop {
+            parse_crop(crop).map_err(|e| anyhow::anyhow!("crop: {e}"))?;
+        }
+        if self
+            .rotate
+            .is_some_and(|rotate| !matches!(rotate, 0 | 90 | 180 | 270))
+        {
+            anyhow::bail!("rotate: expected a rotation of 0, 90, 180 or 270 degrees");
+        }
+        if self.maps.is_some() && (self.width_maps.is_some() || self.height_maps.is_some()) {
+            anyhow::bail!("maps cannot be combined with width_maps or height_maps");
+        }
+        if self.width_maps.is_some() && self.height_maps.is_some() {
+            anyhow::bail!("width_maps cannot be combined with height_maps, use maps instead");
+        }
+        if self
+            .brightness
+            .is_some_and(|brightness| !(-1.0..=1.0).contains(&brightness))
+        {
+            anyhow::bail!("brightness: must be a number from -1 to 1");
+        }
+        if self
+            .gamma
+            .is_some_and(|gamma| !(gamma > 0.0 && gamma.is_finite()))
+        {
+            anyhow::bail!("gamma: must be a positive number");
+        }
+        if self
+            .contrast
+            .is_some_and(|contrast| !(contrast >= 0.0 && contrast.is_finite()))
+        {
+            anyhow::bail!("contrast: must be a number of at least 0");
+        }
+        if self
+            .saturation
+            .is_some_and(|saturation| !(saturation >= 0.0 && saturation.is_finite()))
+        {
+            anyhow::bail!("saturation: must be a number of at least 0");
+        }
+        if self.loop_count == Some(0) {
+            anyhow::bail!("loop_count: must be at least 1");
+        }
+        if self
+            .control_radius
+            .is_some_and(|radius| !(radius > 0.0 && radius.is_finite()))
+        {
+            anyhow::bail!("control_radius: must be a positive number");
+        }
+        Ok(())
+    }
+
+    /// Writes these options out as a project file.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let contents = toml::to_string(self)?;
+        fs::write(path, contents)
+            .with_context(|| format!("failed to write project file {}", path.display()))
+    }
+
+    /// Fills every missing value in `self` with the value from `fallback`.
+    pub fn or(self, fallback: Options) -> Options {
+        Options {
+            top_left: self.top_left.or(fallback.top_left),
+            direction: self.direction.or(fallback.direction),
+            top: self.top.or(fallback.top),
+            starting_index: self.starting_index.or(fallback.starting_index),
+            world: self.world.or(fallback.world),
+            mc_version: self.mc_version.or(fallback.mc_version),
+            display_name: self.display_name.or(fallback.display_name),
+            crop: self.crop.or(fallback.crop),
+            rotate: self.rotate.or(fallback.rotate),
+            flip_horizontal: self.flip_horizontal.or(fallback.flip_horizontal),
+            flip_vertical: self.flip_vertical.or(fallback.flip_vertical),
+            maps: self.maps.or(fallback.maps),
+            width_maps: self.width_maps.or(fallback.width_maps),
+            height_maps: self.height_maps.or(fallback.height_maps),
+            fit: self.fit.or(fallback.fit),
+            filter: self.filter.or(fallback.filter),
+            anchor: self.anchor.or(fallback.anchor),
+            brightness: self.brightness.or(fallback.brightness),
+            contrast: self.contrast.or(fallback.contrast),
+            saturation: self.saturation.or(fallback.saturation),
+            gamma: self.gamma.or(fallback.gamma),
+            auto_levels: self.auto_levels.or(fallback.auto_levels),
+            alpha_threshold: self.alpha_threshold.or(fallback.alpha_threshold),
+            transparent_padding: self.transparent_padding.or(fallback.transparent_padding),
+            dithering: self.dithering.or(fallback.dithering),
+            serpentine: self.serpentine.or(fallback.serpentine),
+            color_metric: self.color_metric.or(fallback.color_metric),
+            linear_light: self.linear_light.or(fallback.linear_light),
+            lookup_table: self.lookup_table.or(fallback.lookup_table),
+            input_path: self.input_path.or(fallback.input_path),
+            start_number: self.start_number.or(fallback.start_number),
+            end_number: self.end_number.or(fallback.end_number),
+            first_frame: self.first_frame.or(fallback.first_frame),
+            last_frame: self.last_frame.or(fallback.last_frame),
+            frame_step: self.frame_step.or(fallback.frame_step),
+            source_fps: self.source_fps.or(fallback.source_fps),
+            target_fps: self.target_fps.or(fallback.target_fps),
+            fps: self.fps.or(fallback.fps),
+            playback: self.playback.or(fallback.playback),
+            loop_count: self.loop_count.or(fallback.loop_count),
+            end_frame: self.end_frame.or(fallback.end_frame),
+            player_controls: self.player_controls.or(fallback.player_controls),
+            control_radius: self.control_radius.or(fallback.control_radius),
+            output_path: self.output_path.or(fallback.output_path),
+        }
+    }
+}
+
+/// Parses a location given as `x,y,z`.
+fn parse_location(s: &str) -> Result<Location, String> {
+    let coords = s
+        .split(',')
+        .map(|c| c.trim().parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    match coords[..] {
+        [x, y, z] => Ok((x, y, z)),
+        _ => Err("expected three comma-separated coordinates".to_string()),
+    }
+}
+
+/// Checks that a crop rectangle is valid, keeping it as given.
+fn parse_crop(s: &str) -> Result<String, String> {
+    s.parse::<Crop>()?;
+    Ok(s.to_string())
+}
+
+/// Parses a rotation in degrees, which must be a multiple of 90.
+fn parse_rotation(s: &str) -> Result<u32, String> {
+    match s.trim().parse::<u32>() {
+        Ok(degrees) if degrees % 90 == 0 => Ok(degrees % 360),
+        _ => Err("expected a rotation of 90, 180 or 270 degrees".to_string()),
+    }
+}
+
+/// Parses a number of maps given as `columns x rows`, e.g. `8x4`.
+fn parse_map_grid(s: &str) -> Result<(u32, u32), String> {
+    let (columns, rows) = s
+        .split_once(['x', 'X'])
+        .ok_or("expected the number of maps as COLUMNSxROWS, e.g. 8x4")?;
+    let columns = columns.trim().parse::<u32>().map_err(|e| e.to_string())?;
+    let rows = rows.trim().parse::<u32>().map_err(|e| e.to_string())?;
+    Ok((columns, rows))
+}
+
+/// Checks that a Minecraft version is supported, keeping it as given.
+fn parse_mc_version(s: &str) -> Result<String, String> {
+    match McVersion::from_name(s) {
+        Some(_) => Ok(s.to_string()),
+        None => Err(format!(
+            "unsupported Minecraft version, the oldest supported is {}",
+            MC_VERSIONS[0].name
+        )),
+    }
+}
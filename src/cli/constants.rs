@@ -1,24 +1,208 @@
+use std::fmt;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::image_processor::colors::{BASE_COLORS_1_16, BASE_COLORS_1_17};
+
 /// Direction enum with values that correspond to Minecraft's NBT `Facing` byte
 #[repr(u8)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Direction {
+    Down = 0,
+    Up = 1,
     North = 2,
     South = 3,
     West = 4,
     East = 5,
 }
 
-impl From<&str> for Direction {
-    fn from(s: &str) -> Self {
-        match s {
-            "north" => Direction::North,
-            "south" => Direction::South,
-            "west" => Direction::West,
-            "east" => Direction::East,
-            _ => Direction::North,
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+impl Direction {
+    /// Whether this direction is up or down, i.e. a floor or ceiling display.
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Direction::Up | Direction::Down)
+    }
+
+    /// The unit vector pointing in this direction, as (x, y, z).
+    pub fn vector(self) -> (i32, i32, i32) {
+        match self {
+            Direction::Down => (0, -1, 0),
+            Direction::Up => (0, 1, 0),
+            Direction::North => (0, 0, -1),
+            Direction::South => (0, 0, 1),
+            Direction::West => (-1, 0, 0),
+            Direction::East => (1, 0, 0),
+        }
+    }
+
+    /// The direction pointing the opposite way.
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Down => Direction::Up,
+            Direction::Up => Direction::Down,
+            horizontal => horizontal.clockwise().clockwise(),
         }
     }
+
+    /// The horizontal direction 90 degrees clockwise when viewed from above.
+    /// Up and down are unchanged.
+    pub fn clockwise(self) -> Direction {
+        match self {
+            Direction::North => Direction::East,
+            Direction::East => Direction::South,
+            Direction::South => Direction::West,
+            Direction::West => Direction::North,
+            vertical => vertical,
+        }
+    }
+
+    /// The horizontal direction 90 degrees counterclockwise when viewed from above.
+    /// Up and down are unchanged.
+    pub fn counterclockwise(self) -> Direction {
+        self.clockwise().clockwise().clockwise()
+    }
 }
 
 /// A 3-tuple representing (x, y, z) coordinates in Minecraft
 pub type Location = (i64, i64, i64);
+
+/// Describes the output format for a range of Minecraft versions,
+/// from `name` up to (but not including) the next profile in `MC_VERSIONS`.
+pub struct McVersion {
+    /// The first Minecraft version this profile applies to.
+    pub name: &'static str,
+
+    /// Used in the `DataVersion` field of the NBT files.
+    pub data_version: i32,
+
+    /// Used in the `pack_format` field of `pack.mcmeta`.
+    pub pack_format: i32,
+
+    /// Whether items store the map ID in a data component (1.20.5+) instead of an NBT tag.
+    pub item_components: bool,
+
+    /// Whether datapack folders have singular names, e.g. `function` instead of `functions` (1.21+).
+    pub singular_folders: bool,
+
+    /// The number of base map colors, which decides the palette.
+    pub base_colors: usize,
+}
+
+/// Every supported version profile, oldest first.
+pub const MC_VERSIONS: [McVersion; 14] = [
+    McVersion::new("1.16.2", 2578, 6, false, false, BASE_COLORS_1_16),
+    McVersion::new("1.17", 2724, 7, false, false, BASE_COLORS_1_17),
+    McVersion::new("1.18", 2860, 8, false, false, BASE_COLORS_1_17),
+    McVersion::new("1.18.2", 2975, 9, false, false, BASE_COLORS_1_17),
+    McVersion::new("1.19", 3105, 10, false, false, BASE_COLORS_1_17),
+    McVersion::new("1.19.4", 3337, 12, false, false, BASE_COLORS_1_17),
+    McVersion::new("1.20", 3463, 15, false, false, BASE_COLORS_1_17),
+    McVersion::new("1.20.2", 3578, 18, false, false, BASE_COLORS_1_17),
+    McVersion::new("1.20.3", 3698, 26, false, false, BASE_COLORS_1_17),
+    McVersion::new("1.20.5", 3837, 41, true, false, BASE_COLORS_1_17),
+    McVersion::new("1.21", 3953, 48, true, true, BASE_COLORS_1_17),
+    McVersion::new("1.21.2", 4080, 57, true, true, BASE_COLORS_1_17),
+    McVersion::new("1.21.4", 4189, 61, true, true, BASE_COLORS_1_17),
+    McVersion::new("1.21.5", 4325, 71, true, true, BASE_COLORS_1_17),
+];
+
+impl McVersion {
+    const fn new(
+        name: &'static str,
+        data_version: i32,
+        pack_format: i32,
+        item_components: bool,
+        singular_folders: bool,
+        base_colors: usize,
+    ) -> Self {
+        McVersion {
+            name,
+            data_version,
+            pack_format,
+            item_components,
+            singular_folders,
+            base_colors,
+        }
+    }
+
+    /// Returns the profile for a Minecraft version such as `1.20.4`,
+    /// i.e. the newest profile that is not newer than the given version.
+    pub fn from_name(name: &str) -> Option<&'static McVersion> {
+        let version = parse_version(name)?;
+        MC_VERSIONS
+            .iter()
+            .rev()
+            .find(|profile| parse_version(profile.name).unwrap() <= version)
+    }
+
+    /// Whether a version such as `1.21.9` is newer than every profile,
+    /// in which case `from_name` falls back to the newest profile, which may not match its format.
+    pub fn is_newer_than_latest(name: &str) -> bool {
+        parse_version(name)
+            .is_some_and(|version| version > parse_version(Self::latest().name).unwrap())
+    }
+
+    /// Returns the profile for a world saved with the given `DataVersion`.
+    pub fn from_data_version(data_version: i32) -> Option<&'static McVersion> {
+        MC_VERSIONS
+            .iter()
+            .rev()
+            .find(|profile| profile.data_version <= data_version)
+    }
+
+    /// Returns the newest supported profile.
+    pub fn latest() -> &'static McVersion {
+        &MC_VERSIONS[MC_VERSIONS.len() - 1]
+    }
+
+    /// The name of the datapack folder containing functions, and of the function tags folder.
+    pub fn function_folder(&self) -> &'static str {
+        if self.singular_folders {
+            "function"
+        } else {
+            "functions"
+        }
+    }
+
+    /// Whether scoreboard objective names can be longer than 16 characters (1.18+).
+    pub fn long_objective_names(&self) -> bool {
+        parse_version(self.name).unwrap() >= (1, 18, 0)
+    }
+
+    /// Returns the SNBT of a filled map item with the given map ID, as used in an item frame's `Item` tag.
+    pub fn map_item(&self, map_id: usize) -> String {
+        if self.item_components {
+            format!("{{id:\"minecraft:filled_map\", components:{{\"minecraft:map_id\":{map_id}}}, count:1}}")
+        } else {
+            format!("{{id:\"minecraft:filled_map\", tag:{{map:{map_id}}}, Count:1b}}")
+        }
+    }
+
+    /// Returns the NBT path to the map ID of the item in an item frame.
+    pub fn map_id_path(&self) -> &'static str {
+        if self.item_components {
+            "Item.components.\"minecraft:map_id\""
+        } else {
+            "Item.tag.map"
+        }
+    }
+}
+
+/// Parses a version such as `1.20.4` into its numeric parts, with a missing patch number being 0.
+fn parse_version(name: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = name.split('.').map(|part| part.parse::<u32>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((major, minor, patch))
+}
@@ -1,20 +1,396 @@
+use std::io::IsTerminal;
 use std::path::PathBuf;
 
+use clap::Parser;
+use inquire::validator::Validation;
 use inquire::CustomType;
 
-use constants::{Direction, Location};
+use crate::image_processor::dithering::Dithering;
+use crate::image_processor::lookup_table::LookupTable;
+use crate::image_processor::metric::ColorMetric;
+use crate::image_processor::preprocess::Preprocessing;
+use crate::image_processor::resize::{Anchor, Fit, ResizeFilter};
+use crate::image_processor::tone::ToneAdjustments;
+use crate::image_processor::ProcessorOptions;
+use crate::input::InputOptions;
+use crate::output_generator::display::Display;
+use crate::output_generator::playback::{EndFrame, Playback, PlaybackMode};
+use crate::output_generator::world::World;
+use config::Options;
+use constants::{Direction, Location, McVersion, MC_VERSIONS};
 
+pub mod config;
 pub mod constants;
 
 pub struct CliArgs {
     pub top_left: Location,
     pub direction: Direction,
+    pub top: Direction,
     pub starting_index: usize,
     pub input_path: PathBuf,
+    pub input: InputOptions,
+    /// The frame rate the datapack plays the animation at, if not from the delays of the frames.
+    pub fps: Option<f64>,
+    pub playback: Playback,
+    pub display: Display,
     pub output_path: PathBuf,
+    /// The world save being installed into, if any. The output path is then the world itself.
+    pub world: Option<PathBuf>,
+    pub version: &'static McVersion,
+    pub processing: ProcessorOptions,
+}
+
+/// Command line flags. Every option is optional; missing values are taken from the project file
+/// if one is given, then prompted for when stdin is a terminal, and are an error otherwise.
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Converts images to Minecraft maps and an animation datapack"
+)]
+struct Flags {
+    /// Project file to read options from (flags take precedence)
+    #[arg(short, long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Write the final options to this project file
+    #[arg(long, value_name = "FILE")]
+    save_config: Option<PathBuf>,
+
+    /// Never prompt; fail if a required value is missing
+    #[arg(long)]
+    non_interactive: bool,
+
+    #[command(flatten)]
+    options: Options,
 }
 
 pub fn run() -> anyhow::Result<CliArgs> {
+    let flags = Flags::parse();
+    let mut prompter = Prompter {
+        not_interactive: if flags.non_interactive {
+            Some("--non-interactive was given")
+        } else if !std::io::stdin().is_terminal() {
+            Some("stdin is not a terminal")
+        } else {
+            None
+        },
+        prompted: false,
+    };
+
+    let options = match &flags.config {
+        Some(path) => {
+            let mut file = Options::load(path)?;
+            // A world replaces the output folder and starting index, so whichever of them
+            // is given as a flag takes precedence over the others in the file
+            if flags.options.world.is_some() {
+                file.output_path = None;
+                file.starting_index = None;
+            }
+            if flags.options.output_path.is_some() || flags.options.starting_index.is_some() {
+                file.world = None;
+            }
+            // Likewise for a grid of maps and its individual dimensions
+            if flags.options.maps.is_some()
+                || flags.options.width_maps.is_some()
+                || flags.options.height_maps.is_some()
+            {
+                file.maps = None;
+                file.width_maps = None;
+                file.height_maps = None;
+            }
+            flags.options.or(file)
+        }
+        None => flags.options,
+    };
+    options.validate()?;
+    let top_left = match options.top_left {
+        Some(top_left) => top_left,
+        None => {
+            prompter.require("--top-left")?;
+            prompt_top_left()?
+        }
+    };
+
+    let direction = match options.direction {
+        Some(direction) => direction,
+        None => {
+            prompter.require("--direction")?;
+            prompt_direction()?
+        }
+    };
+
+    // Only floor and ceiling displays need to know which way is up in the image
+    let top = match options.top {
+        Some(top) => Some(top),
+        None if direction.is_vertical() => {
+            prompter.require("--top")?;
+            Some(prompt_top()?)
+        }
+        None => None,
+    };
+
+    // When installing into a world, the world decides where the files go and which map IDs are free
+    let world = match &options.world {
+        Some(path) => Some(World::open(path)?),
+        None => None,
+    };
+
+    let starting_index = match (&world, options.starting_index) {
+        (Some(world), _) => {
+            let starting_index = world.next_map_id()?;
+            println!(
+                "Installing into world \"{}\", starting at map {starting_index}",
+                world.name
+            );
+            starting_index
+        }
+        (None, Some(starting_index)) => starting_index,
+        (None, None) => {
+            prompter.require("--starting-index")?;
+            prompt_starting_index()?
+        }
+    };
+
+    // Use the version the world was last saved in, unless told otherwise
+    let mc_version = match (
+        options.mc_version,
+        world.as_ref().and_then(|w| w.data_version),
+    ) {
+        (Some(mc_version), _) => mc_version,
+        (None, Some(data_version)) => match McVersion::from_data_version(data_version) {
+            Some(profile) => {
+                if data_version > McVersion::latest().data_version {
+                    println!(
+                        "Warning: the world was saved in a newer Minecraft version (data version {data_version}) than the newest supported, {}. The files may not load",
+                        profile.name
+                    );
+                }
+                profile.name.to_string()
+            }
+            None => anyhow::bail!("the world was saved in an unsupported Minecraft version"),
+        },
+        (None, None) => {
+            prompter.require("--mc-version")?;
+            prompt_mc_version()?
+        }
+    };
+    let version = McVersion::from_name(&mc_version).ok_or_else(|| {
+        anyhow::anyhow!(
+            "unsupported Minecraft version {mc_version}, the oldest supported is {}",
+            MC_VERSIONS[0].name
+        )
+    })?;
+    if McVersion::is_newer_than_latest(&mc_version) {
+        println!(
+            "Warning: Minecraft {mc_version} is newer than the newest supported version, {}. The files may not load",
+            version.name
+        );
+    }
+    println!(
+        "Generating files for Minecraft {} (data version {}, pack format {})",
+        mc_version, version.data_version, version.pack_format
+    );
+
+    let display = Display::new(options.display_name.clone())?;
+    // Objective names were limited to 16 characters before 1.18, and the triggers are named after the display
+    let player_controls = options
+        .player_controls
+        .unwrap_or(options.control_radius.is_some());
+    if player_controls
+        && !version.long_objective_names()
+        && display.namespace().len() + "_control".len() > 16
+    {
+        anyhow::bail!(
+            "the display name must be at most 8 characters for player controls before Minecraft 1.18"
+        );
+    }
+
+    let input_path = match options.input_path {
+        Some(input_path) => input_path,
+        None => {
+            prompter.require("--input")?;
+            PathBuf::from(
+                inquire::Text::new("Enter the path to the input folder or animation")
+                    .with_help_message(
+                        "This should be a folder containing the images you want to convert, a file name pattern like frame_%05d.png, a text file listing the frames, or an animated GIF, PNG or WebP file",
+                    )
+                    .with_default("in/")
+                    .prompt()?,
+            )
+        }
+    };
+
+    let output_path = match (&options.world, options.output_path) {
+        (Some(world), _) => world.clone(),
+        (None, Some(output_path)) => output_path,
+        (None, None) => {
+            prompter.require("--output")?;
+            PathBuf::from(
+                inquire::Text::new("Enter the path to the output folder")
+                    .with_help_message("This folder will contain the data/ and datapacks/ folders")
+                    .with_default("out/")
+                    .prompt()?,
+            )
+        }
+    };
+
+    let answers = Options {
+        top_left: Some(top_left),
+        direction: Some(direction),
+        top,
+        starting_index: world.is_none().then_some(starting_index),
+        world: options.world.clone(),
+        mc_version: Some(mc_version),
+        display_name: options.display_name.clone(),
+        crop: options.crop.clone(),
+        rotate: options.rotate,
+        flip_horizontal: options.flip_horizontal,
+        flip_vertical: options.flip_vertical,
+        maps: options.maps,
+        width_maps: options.width_maps,
+        height_maps: options.height_maps,
+        fit: options.fit,
+        filter: options.filter,
+        anchor: options.anchor,
+        brightness: options.brightness,
+        contrast: options.contrast,
+        saturation: options.saturation,
+        gamma: options.gamma,
+        auto_levels: options.auto_levels,
+        alpha_threshold: options.alpha_threshold,
+        transparent_padding: options.transparent_padding,
+        dithering: options.dithering,
+        serpentine: options.serpentine,
+        color_metric: options.color_metric,
+        linear_light: options.linear_light,
+        lookup_table: options.lookup_table,
+        input_path: Some(input_path.clone()),
+        start_number: options.start_number,
+        end_number: options.end_number,
+        first_frame: options.first_frame,
+        last_frame: options.last_frame,
+        frame_step: options.frame_step,
+        source_fps: options.source_fps,
+        target_fps: options.target_fps,
+        fps: options.fps,
+        playback: options.playback,
+        loop_count: options.loop_count,
+        end_frame: options.end_frame,
+        player_controls: options.player_controls,
+        control_radius: options.control_radius,
+        output_path: world.is_none().then(|| output_path.clone()),
+    };
+
+    // Offer to save the answers if anything had to be prompted for
+    let save_path = match flags.save_config {
+        Some(path) => Some(path),
+        None if prompter.prompted => prompt_save_path()?,
+        None => None,
+    };
+    if let Some(path) = save_path {
+        answers.save(&path)?;
+        println!("Saved project file to {}", path.display());
+    }
+
+    // A grid of maps given as `--maps` takes precedence over the individual dimensions
+    let map_columns = options
+        .maps
+        .map(|(columns, _)| columns)
+        .or(options.width_maps);
+    let map_rows = options.maps.map(|(_, rows)| rows).or(options.height_maps);
+    let default_fit = if map_columns.is_some() || map_rows.is_some() {
+        Fit::Contain
+    } else {
+        Fit::None
+    };
+    // Ping-pong finishes back at the start, so it stays there instead of jumping to the other end
+    let playback = options.playback.unwrap_or(PlaybackMode::Loop);
+    let default_end_frame = match playback {
+        PlaybackMode::PingPong => EndFrame::First,
+        _ => EndFrame::Last,
+    };
+
+    Ok(CliArgs {
+        top_left,
+        direction,
+        top: top.unwrap_or(Direction::North),
+        starting_index,
+        input_path,
+        input: InputOptions {
+            start_number: options.start_number,
+            end_number: options.end_number,
+            first_frame: options.first_frame,
+            last_frame: options.last_frame,
+            frame_step: options.frame_step.unwrap_or(1),
+            source_fps: options.source_fps,
+            target_fps: options.target_fps,
+        },
+        fps: options.fps,
+        playback: Playback {
+            mode: playback,
+            loop_count: options.loop_count,
+            end_frame: options.end_frame.unwrap_or(default_end_frame),
+            player_controls,
+            control_radius: options.control_radius,
+        },
+        display,
+        output_path,
+        world: options.world,
+        version,
+        processing: ProcessorOptions {
+            alpha_threshold: options.alpha_threshold.unwrap_or(128),
+            transparent_padding: options.transparent_padding.unwrap_or(false),
+            dithering: options.dithering.unwrap_or(Dithering::FloydSteinberg),
+            serpentine: options.serpentine.unwrap_or(false),
+            metric: options.color_metric.unwrap_or(ColorMetric::Srgb),
+            linear_light: options.linear_light.unwrap_or(false),
+            lookup_table: options.lookup_table.unwrap_or(LookupTable::None),
+            preprocessing: Preprocessing {
+                crop: options
+                    .crop
+                    .map(|crop| crop.parse())
+                    .transpose()
+                    .map_err(anyhow::Error::msg)?,
+                rotation: options.rotate.unwrap_or(0),
+                flip_horizontal: options.flip_horizontal.unwrap_or(false),
+                flip_vertical: options.flip_vertical.unwrap_or(false),
+            },
+            tone: ToneAdjustments::new(
+                options.brightness.unwrap_or(0.0),
+                options.contrast.unwrap_or(1.0),
+                options.saturation.unwrap_or(1.0),
+                options.gamma.unwrap_or(1.0),
+                options.auto_levels.unwrap_or(false),
+            ),
+            map_columns,
+            map_rows,
+            fit: options.fit.unwrap_or(default_fit),
+            filter: options.filter.unwrap_or(ResizeFilter::Lanczos3),
+            anchor: options.anchor.unwrap_or(Anchor::Center),
+        },
+    })
+}
+
+/// Keeps track of whether we may prompt for missing values, and whether we have.
+struct Prompter {
+    /// Why we may not prompt for missing values, if we may not.
+    not_interactive: Option<&'static str>,
+    prompted: bool,
+}
+
+impl Prompter {
+    /// Called before prompting for a missing value.
+    /// Errors if we are not allowed to prompt for it.
+    fn require(&mut self, flag: &str) -> anyhow::Result<()> {
+        if let Some(reason) = self.not_interactive {
+            anyhow::bail!("missing required argument {flag} ({reason})");
+        }
+        self.prompted = true;
+        Ok(())
+    }
+}
+
+fn prompt_top_left() -> anyhow::Result<Location> {
     let x = CustomType::<i64>::new("Enter the x-coordinate of the top left corner")
         .with_error_message("Please enter a valid integer")
         .with_default(0)
@@ -30,45 +406,82 @@ pub fn run() -> anyhow::Result<CliArgs> {
         .with_default(0)
         .prompt()?;
 
-    let direction_opts = vec!["north", "east", "south", "west", "up", "down"];
-    let direction =
-        inquire::Select::new("Select the direction the maps will facing", direction_opts)
-            .with_starting_cursor(0)
-            .with_help_message("This can be found in the F3 menu by looking towards a direction")
-            .prompt()?;
+    Ok((x, y, z))
+}
 
-    let starting_index = CustomType::<usize>::new("Enter the starting index of the maps")
-        .with_error_message("Please enter a valid integer")
-        .with_help_message(
-            "If you already have maps in the world, this should be the index of the next map",
-        )
-        .with_default(0)
-        .prompt()?;
+fn prompt_direction() -> anyhow::Result<Direction> {
+    let direction = inquire::Select::new(
+        "Select the direction the maps will facing",
+        vec![
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+            Direction::Up,
+            Direction::Down,
+        ],
+    )
+    .with_starting_cursor(0)
+    .with_help_message("This can be found in the F3 menu by looking towards a direction")
+    .prompt()?;
+    Ok(direction)
+}
 
-    let input_path = inquire::Text::new("Enter the path to the input folder")
-        .with_help_message("This should be a folder containing the images you want to convert")
-        .with_default("in/")
-        .prompt()?;
+fn prompt_top() -> anyhow::Result<Direction> {
+    let top_opts = vec![
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ];
+    let top = inquire::Select::new(
+        "Select the direction the top of the image will point to",
+        top_opts,
+    )
+    .with_starting_cursor(0)
+    .with_help_message("The maps will be rotated so the image reads correctly from this side")
+    .prompt()?;
+    Ok(top)
+}
 
-    let output_path = inquire::Text::new("Enter the path to the output folder")
-        .with_help_message("This folder will contain the data/ and datapacks/ folders")
-        .with_default("out/")
-        .prompt()?;
+fn prompt_starting_index() -> anyhow::Result<usize> {
+    Ok(
+        CustomType::<usize>::new("Enter the starting index of the maps")
+            .with_error_message("Please enter a valid integer")
+            .with_help_message(
+                "If you already have maps in the world, this should be the index of the next map",
+            )
+            .with_default(0)
+            .prompt()?,
+    )
+}
 
-    // let confirmation =
-    //     inquire::Confirm::new("All contents of the output folder will be overwritten. Continue?")
-    //         .with_default(true)
-    //         .prompt()?;
-    //
-    // if !confirmation {
-    //     anyhow::bail!("user cancelled");
-    // }
+fn prompt_mc_version() -> anyhow::Result<String> {
+    Ok(
+        inquire::Text::new("Enter the Minecraft version to generate files for")
+            .with_help_message("The maps and datapack are only compatible with this version")
+            .with_default(McVersion::latest().name)
+            .with_validator(|version: &str| {
+                Ok(match McVersion::from_name(version) {
+                    Some(_) => Validation::Valid,
+                    None => Validation::Invalid("Unsupported Minecraft version".into()),
+                })
+            })
+            .prompt()?,
+    )
+}
 
-    Ok(CliArgs {
-        top_left: (x, y, z),
-        direction: direction.into(),
-        starting_index,
-        input_path: PathBuf::from(input_path),
-        output_path: PathBuf::from(output_path),
-    })
+fn prompt_save_path() -> anyhow::Result<Option<PathBuf>> {
+    let save = inquire::Confirm::new("Save these answers to a project file?")
+        .with_help_message("The project file can be passed with --config to repeat this run")
+        .with_default(false)
+        .prompt()?;
+    if !save {
+        return Ok(None);
+    }
+
+    let path = inquire::Text::new("Enter the path to the project file")
+        .with_default("mapmaker.toml")
+        .prompt()?;
+    Ok(Some(PathBuf::from(path)))
 }
@@ -2,307 +2,537 @@ use image::Rgb;
 use kd_tree::KdTree3;
 use typenum::U3;
 
-use lazy_static::lazy_static;
-
-/// The number of colors that Minecraft supports, excluding the 4 transparent ones.
-const COLOR_COUNT: usize = 244;
+use crate::image_processor::lookup_table::ColorLookupTable;
+use crate::image_processor::metric::ColorMetric;
 
 pub const BLACK_INDEX: MapColor = 119;
 
+/// Map colors 0 to 3 are all transparent.
+pub const TRANSPARENT_INDEX: MapColor = 0;
+
+/// The number of base colors added in 1.16 (including the transparent one), up to the warped wart block.
+pub const BASE_COLORS_1_16: usize = 59;
+
+/// The number of base colors added in 1.17 (including the transparent one), up to glow lichen.
+pub const BASE_COLORS_1_17: usize = 62;
+
+/// Every base color of Minecraft maps, indexed by base color ID.
+/// Base color 0 is transparent and never used for matching.
+/// Newer versions only ever append colors, so a version's palette is a prefix of this array.
+const BASE_COLORS: [Rgb<u8>; BASE_COLORS_1_17] = [
+    Rgb([0, 0, 0]),       // NONE
+    Rgb([127, 178, 56]),  // GRASS
+    Rgb([247, 233, 163]), // SAND
+    Rgb([199, 199, 199]), // WOOL
+    Rgb([255, 0, 0]),     // FIRE
+    Rgb([160, 160, 255]), // ICE
+    Rgb([167, 167, 167]), // METAL
+    Rgb([0, 124, 0]),     // PLANT
+    Rgb([255, 255, 255]), // SNOW
+    Rgb([164, 168, 184]), // CLAY
+    Rgb([151, 109, 77]),  // DIRT
+    Rgb([112, 112, 112]), // STONE
+    Rgb([64, 64, 255]),   // WATER
+    Rgb([143, 119, 72]),  // WOOD
+    Rgb([255, 252, 245]), // QUARTZ
+    Rgb([216, 127, 51]),  // COLOR_ORANGE
+    Rgb([178, 76, 216]),  // COLOR_MAGENTA
+    Rgb([102, 153, 216]), // COLOR_LIGHT_BLUE
+    Rgb([229, 229, 51]),  // COLOR_YELLOW
+    Rgb([127, 204, 25]),  // COLOR_LIGHT_GREEN
+    Rgb([242, 127, 165]), // COLOR_PINK
+    Rgb([76, 76, 76]),    // COLOR_GRAY
+    Rgb([153, 153, 153]), // COLOR_LIGHT_GRAY
+    Rgb([76, 127, 153]),  // COLOR_CYAN
+    Rgb([127, 63, 178]),  // COLOR_PURPLE
+    Rgb([51, 76, 178]),   // COLOR_BLUE
+    Rgb([102, 76, 51]),   // COLOR_BROWN
+    Rgb([102, 127, 51]),  // COLOR_GREEN
+    Rgb([153, 51, 51]),   // COLOR_RED
+    Rgb([25, 25, 25]),    // COLOR_BLACK
+    Rgb([250, 238, 77]),  // GOLD
+    Rgb([92, 219, 213]),  // DIAMOND
+    Rgb([74, 128, 255]),  // LAPIS
+    Rgb([0, 217, 58]),    // EMERALD
+    Rgb([129, 86, 49]),   // PODZOL
+    Rgb([112, 2, 0]),     // NETHER
+    Rgb([209, 177, 161]), // TERRACOTTA_WHITE
+    Rgb([159, 82, 36]),   // TERRACOTTA_ORANGE
+    Rgb([149, 87, 108]),  // TERRACOTTA_MAGENTA
+    Rgb([112, 108, 138]), // TERRACOTTA_LIGHT_BLUE
+    Rgb([186, 133, 36]),  // TERRACOTTA_YELLOW
+    Rgb([103, 117, 53]),  // TERRACOTTA_LIGHT_GREEN
+    Rgb([160, 77, 78]),   // TERRACOTTA_PINK
+    Rgb([57, 41, 35]),    // TERRACOTTA_GRAY
+    Rgb([135, 107, 98]),  // TERRACOTTA_LIGHT_GRAY
+    Rgb([87, 92, 92]),    // TERRACOTTA_CYAN
+    Rgb([122, 73, 88]),   // TERRACOTTA_PURPLE
+    Rgb([76, 62, 92]),    // TERRACOTTA_BLUE
+    Rgb([76, 50, 35]),    // TERRACOTTA_BROWN
+    Rgb([76, 82, 42]),    // TERRACOTTA_GREEN
+    Rgb([142, 60, 46]),   // TERRACOTTA_RED
+    Rgb([37, 22, 16]),    // TERRACOTTA_BLACK
+    Rgb([189, 48, 49]),   // CRIMSON_NYLIUM
+    Rgb([148, 63, 97]),   // CRIMSON_STEM
+    Rgb([92, 25, 29]),    // CRIMSON_HYPHAE
+    Rgb([22, 126, 134]),  // WARPED_NYLIUM
+    Rgb([58, 142, 140]),  // WARPED_STEM
+    Rgb([86, 44, 62]),    // WARPED_HYPHAE
+    Rgb([20, 180, 133]),  // WARPED_WART_BLOCK
+    Rgb([100, 100, 100]), // DEEPSLATE
+    Rgb([216, 175, 147]), // RAW_IRON
+    Rgb([127, 167, 150]), // GLOW_LICHEN
+];
+
+/// Each base color appears on maps in four shades, which are the base color multiplied by these
+/// values and divided by 255. The map color ID is `base_color * 4 + shade`.
+const SHADE_MULTIPLIERS: [u32; 4] = [180, 220, 255, 135];
+
 /// A wrapper around `Rgb<u8>` that implements the `KdPoint` trait for use with the `kd-tree`.
-/// Contains the original `Rgb<u8>` value and the index of that color in the Minecraft color list.
+/// Contains the original `Rgb<u8>` value, the index of that color in the Minecraft color list,
+/// and the coordinates of the color in the space of the `ColorMetric` in use.
 #[derive(Debug, Clone, Copy)]
-pub struct MinecraftRgb(Rgb<u8>, MapColor);
+pub struct MinecraftRgb(Rgb<u8>, MapColor, [f32; 3]);
 impl kd_tree::KdPoint for MinecraftRgb {
-    type Scalar = isize;
+    type Scalar = f32;
     type Dim = U3;
 
     fn at(&self, k: usize) -> Self::Scalar {
-        self.0[k] as isize
+        self.2[k]
     }
 }
 
-/// This array contains `COLOR_COUNT` entries, indexed starting from 4 (colors 0-3 are transparent).
-/// Each element in this array represents the corresponding Minecraft map color at that index.
-/// As of Minecraft 1.17, there are 244 colours in this array.
-#[allow(overflowing_literals)]
-const COLOR_LIST: [MinecraftRgb; COLOR_COUNT] = [
-    MinecraftRgb(Rgb([89, 125, 39]), 4),
-    MinecraftRgb(Rgb([109, 153, 48]), 5),
-    MinecraftRgb(Rgb([127, 178, 56]), 6),
-    MinecraftRgb(Rgb([67, 94, 29]), 7),
-    MinecraftRgb(Rgb([174, 164, 115]), 8),
-    MinecraftRgb(Rgb([213, 201, 140]), 9),
-    MinecraftRgb(Rgb([247, 233, 163]), 10),
-    MinecraftRgb(Rgb([130, 123, 86]), 11),
-    MinecraftRgb(Rgb([140, 140, 140]), 12),
-    MinecraftRgb(Rgb([171, 171, 171]), 13),
-    MinecraftRgb(Rgb([199, 199, 199]), 14),
-    MinecraftRgb(Rgb([105, 105, 105]), 15),
-    MinecraftRgb(Rgb([180, 0, 0]), 16),
-    MinecraftRgb(Rgb([220, 0, 0]), 17),
-    MinecraftRgb(Rgb([255, 0, 0]), 18),
-    MinecraftRgb(Rgb([135, 0, 0]), 19),
-    MinecraftRgb(Rgb([112, 112, 180]), 20),
-    MinecraftRgb(Rgb([138, 138, 220]), 21),
-    MinecraftRgb(Rgb([160, 160, 255]), 22),
-    MinecraftRgb(Rgb([84, 84, 135]), 23),
-    MinecraftRgb(Rgb([117, 117, 117]), 24),
-    MinecraftRgb(Rgb([144, 144, 144]), 25),
-    MinecraftRgb(Rgb([167, 167, 167]), 26),
-    MinecraftRgb(Rgb([88, 88, 88]), 27),
-    MinecraftRgb(Rgb([0, 87, 0]), 28),
-    MinecraftRgb(Rgb([0, 106, 0]), 29),
-    MinecraftRgb(Rgb([0, 124, 0]), 30),
-    MinecraftRgb(Rgb([0, 65, 0]), 31),
-    MinecraftRgb(Rgb([180, 180, 180]), 32),
-    MinecraftRgb(Rgb([220, 220, 220]), 33),
-    MinecraftRgb(Rgb([255, 255, 255]), 34),
-    MinecraftRgb(Rgb([135, 135, 135]), 35),
-    MinecraftRgb(Rgb([115, 118, 129]), 36),
-    MinecraftRgb(Rgb([141, 144, 158]), 37),
-    MinecraftRgb(Rgb([164, 168, 184]), 38),
-    MinecraftRgb(Rgb([86, 88, 97]), 39),
-    MinecraftRgb(Rgb([106, 76, 54]), 40),
-    MinecraftRgb(Rgb([130, 94, 66]), 41),
-    MinecraftRgb(Rgb([151, 109, 77]), 42),
-    MinecraftRgb(Rgb([79, 57, 40]), 43),
-    MinecraftRgb(Rgb([79, 79, 79]), 44),
-    MinecraftRgb(Rgb([96, 96, 96]), 45),
-    MinecraftRgb(Rgb([112, 112, 112]), 46),
-    MinecraftRgb(Rgb([59, 59, 59]), 47),
-    MinecraftRgb(Rgb([45, 45, 180]), 48),
-    MinecraftRgb(Rgb([55, 55, 220]), 49),
-    MinecraftRgb(Rgb([64, 64, 255]), 50),
-    MinecraftRgb(Rgb([33, 33, 135]), 51),
-    MinecraftRgb(Rgb([100, 84, 50]), 52),
-    MinecraftRgb(Rgb([123, 102, 62]), 53),
-    MinecraftRgb(Rgb([143, 119, 72]), 54),
-    MinecraftRgb(Rgb([75, 63, 38]), 55),
-    MinecraftRgb(Rgb([180, 177, 172]), 56),
-    MinecraftRgb(Rgb([220, 217, 211]), 57),
-    MinecraftRgb(Rgb([255, 252, 245]), 58),
-    MinecraftRgb(Rgb([135, 133, 129]), 59),
-    MinecraftRgb(Rgb([152, 89, 36]), 60),
-    MinecraftRgb(Rgb([186, 109, 44]), 61),
-    MinecraftRgb(Rgb([216, 127, 51]), 62),
-    MinecraftRgb(Rgb([114, 67, 27]), 63),
-    MinecraftRgb(Rgb([125, 53, 152]), 64),
-    MinecraftRgb(Rgb([153, 65, 186]), 65),
-    MinecraftRgb(Rgb([178, 76, 216]), 66),
-    MinecraftRgb(Rgb([94, 40, 114]), 67),
-    MinecraftRgb(Rgb([72, 108, 152]), 68),
-    MinecraftRgb(Rgb([88, 132, 186]), 69),
-    MinecraftRgb(Rgb([102, 153, 216]), 70),
-    MinecraftRgb(Rgb([54, 81, 114]), 71),
-    MinecraftRgb(Rgb([161, 161, 36]), 72),
-    MinecraftRgb(Rgb([197, 197, 44]), 73),
-    MinecraftRgb(Rgb([229, 229, 51]), 74),
-    MinecraftRgb(Rgb([121, 121, 27]), 75),
-    MinecraftRgb(Rgb([89, 144, 17]), 76),
-    MinecraftRgb(Rgb([109, 176, 21]), 77),
-    MinecraftRgb(Rgb([127, 204, 25]), 78),
-    MinecraftRgb(Rgb([67, 108, 13]), 79),
-    MinecraftRgb(Rgb([170, 89, 116]), 80),
-    MinecraftRgb(Rgb([208, 109, 142]), 81),
-    MinecraftRgb(Rgb([242, 127, 165]), 82),
-    MinecraftRgb(Rgb([128, 67, 87]), 83),
-    MinecraftRgb(Rgb([53, 53, 53]), 84),
-    MinecraftRgb(Rgb([65, 65, 65]), 85),
-    MinecraftRgb(Rgb([76, 76, 76]), 86),
-    MinecraftRgb(Rgb([40, 40, 40]), 87),
-    MinecraftRgb(Rgb([108, 108, 108]), 88),
-    MinecraftRgb(Rgb([132, 132, 132]), 89),
-    MinecraftRgb(Rgb([153, 153, 153]), 90),
-    MinecraftRgb(Rgb([81, 81, 81]), 91),
-    MinecraftRgb(Rgb([53, 89, 108]), 92),
-    MinecraftRgb(Rgb([65, 109, 132]), 93),
-    MinecraftRgb(Rgb([76, 127, 153]), 94),
-    MinecraftRgb(Rgb([40, 67, 81]), 95),
-    MinecraftRgb(Rgb([89, 44, 125]), 96),
-    MinecraftRgb(Rgb([109, 54, 153]), 97),
-    MinecraftRgb(Rgb([127, 63, 178]), 98),
-    MinecraftRgb(Rgb([67, 33, 94]), 99),
-    MinecraftRgb(Rgb([36, 53, 125]), 100),
-    MinecraftRgb(Rgb([44, 65, 153]), 101),
-    MinecraftRgb(Rgb([51, 76, 178]), 102),
-    MinecraftRgb(Rgb([27, 40, 94]), 103),
-    MinecraftRgb(Rgb([72, 53, 36]), 104),
-    MinecraftRgb(Rgb([88, 65, 44]), 105),
-    MinecraftRgb(Rgb([102, 76, 51]), 106),
-    MinecraftRgb(Rgb([54, 40, 27]), 107),
-    MinecraftRgb(Rgb([72, 89, 36]), 108),
-    MinecraftRgb(Rgb([88, 109, 44]), 109),
-    MinecraftRgb(Rgb([102, 127, 51]), 110),
-    MinecraftRgb(Rgb([54, 67, 27]), 111),
-    MinecraftRgb(Rgb([108, 36, 36]), 112),
-    MinecraftRgb(Rgb([132, 44, 44]), 113),
-    MinecraftRgb(Rgb([153, 51, 51]), 114),
-    MinecraftRgb(Rgb([81, 27, 27]), 115),
-    MinecraftRgb(Rgb([17, 17, 17]), 116),
-    MinecraftRgb(Rgb([21, 21, 21]), 117),
-    MinecraftRgb(Rgb([25, 25, 25]), 118),
-    MinecraftRgb(Rgb([13, 13, 13]), 119),
-    MinecraftRgb(Rgb([176, 168, 54]), 120),
-    MinecraftRgb(Rgb([215, 205, 66]), 121),
-    MinecraftRgb(Rgb([250, 238, 77]), 122),
-    MinecraftRgb(Rgb([132, 126, 40]), 123),
-    MinecraftRgb(Rgb([64, 154, 150]), 124),
-    MinecraftRgb(Rgb([79, 188, 183]), 125),
-    MinecraftRgb(Rgb([92, 219, 213]), 126),
-    MinecraftRgb(Rgb([48, 115, 112]), 127),
-    MinecraftRgb(Rgb([52, 90, 180]), 128),
-    MinecraftRgb(Rgb([63, 110, 220]), 129),
-    MinecraftRgb(Rgb([74, 128, 255]), 130),
-    MinecraftRgb(Rgb([39, 67, 135]), 131),
-    MinecraftRgb(Rgb([0, 153, 40]), 132),
-    MinecraftRgb(Rgb([0, 187, 50]), 133),
-    MinecraftRgb(Rgb([0, 217, 58]), 134),
-    MinecraftRgb(Rgb([0, 114, 30]), 135),
-    MinecraftRgb(Rgb([91, 60, 34]), 136),
-    MinecraftRgb(Rgb([111, 74, 42]), 137),
-    MinecraftRgb(Rgb([129, 86, 49]), 138),
-    MinecraftRgb(Rgb([68, 45, 25]), 139),
-    MinecraftRgb(Rgb([79, 1, 0]), 140),
-    MinecraftRgb(Rgb([96, 1, 0]), 141),
-    MinecraftRgb(Rgb([112, 2, 0]), 142),
-    MinecraftRgb(Rgb([59, 1, 0]), 143),
-    MinecraftRgb(Rgb([147, 124, 113]), 144),
-    MinecraftRgb(Rgb([180, 152, 138]), 145),
-    MinecraftRgb(Rgb([209, 177, 161]), 146),
-    MinecraftRgb(Rgb([110, 93, 85]), 147),
-    MinecraftRgb(Rgb([112, 57, 25]), 148),
-    MinecraftRgb(Rgb([137, 70, 31]), 149),
-    MinecraftRgb(Rgb([159, 82, 36]), 150),
-    MinecraftRgb(Rgb([84, 43, 19]), 151),
-    MinecraftRgb(Rgb([105, 61, 76]), 152),
-    MinecraftRgb(Rgb([128, 75, 93]), 153),
-    MinecraftRgb(Rgb([149, 87, 108]), 154),
-    MinecraftRgb(Rgb([78, 46, 57]), 155),
-    MinecraftRgb(Rgb([79, 76, 97]), 156),
-    MinecraftRgb(Rgb([96, 93, 119]), 157),
-    MinecraftRgb(Rgb([112, 108, 138]), 158),
-    MinecraftRgb(Rgb([59, 57, 73]), 159),
-    MinecraftRgb(Rgb([131, 93, 25]), 160),
-    MinecraftRgb(Rgb([160, 114, 31]), 161),
-    MinecraftRgb(Rgb([186, 133, 36]), 162),
-    MinecraftRgb(Rgb([98, 70, 19]), 163),
-    MinecraftRgb(Rgb([72, 82, 37]), 164),
-    MinecraftRgb(Rgb([88, 100, 45]), 165),
-    MinecraftRgb(Rgb([103, 117, 53]), 166),
-    MinecraftRgb(Rgb([54, 61, 28]), 167),
-    MinecraftRgb(Rgb([112, 54, 55]), 168),
-    MinecraftRgb(Rgb([138, 66, 67]), 169),
-    MinecraftRgb(Rgb([160, 77, 78]), 170),
-    MinecraftRgb(Rgb([84, 40, 41]), 171),
-    MinecraftRgb(Rgb([40, 28, 24]), 172),
-    MinecraftRgb(Rgb([49, 35, 30]), 173),
-    MinecraftRgb(Rgb([57, 41, 35]), 174),
-    MinecraftRgb(Rgb([30, 21, 18]), 175),
-    MinecraftRgb(Rgb([95, 75, 69]), 176),
-    MinecraftRgb(Rgb([116, 92, 84]), 177),
-    MinecraftRgb(Rgb([135, 107, 98]), 178),
-    MinecraftRgb(Rgb([71, 56, 51]), 179),
-    MinecraftRgb(Rgb([61, 64, 64]), 180),
-    MinecraftRgb(Rgb([75, 79, 79]), 181),
-    MinecraftRgb(Rgb([87, 92, 92]), 182),
-    MinecraftRgb(Rgb([46, 48, 48]), 183),
-    MinecraftRgb(Rgb([86, 51, 62]), 184),
-    MinecraftRgb(Rgb([105, 62, 75]), 185),
-    MinecraftRgb(Rgb([122, 73, 88]), 186),
-    MinecraftRgb(Rgb([64, 38, 46]), 187),
-    MinecraftRgb(Rgb([53, 43, 64]), 188),
-    MinecraftRgb(Rgb([65, 53, 79]), 189),
-    MinecraftRgb(Rgb([76, 62, 92]), 190),
-    MinecraftRgb(Rgb([40, 32, 48]), 191),
-    MinecraftRgb(Rgb([53, 35, 24]), 192),
-    MinecraftRgb(Rgb([65, 43, 30]), 193),
-    MinecraftRgb(Rgb([76, 50, 35]), 194),
-    MinecraftRgb(Rgb([40, 26, 18]), 195),
-    MinecraftRgb(Rgb([53, 57, 29]), 196),
-    MinecraftRgb(Rgb([65, 70, 36]), 197),
-    MinecraftRgb(Rgb([76, 82, 42]), 198),
-    MinecraftRgb(Rgb([40, 43, 22]), 199),
-    MinecraftRgb(Rgb([100, 42, 32]), 200),
-    MinecraftRgb(Rgb([122, 51, 39]), 201),
-    MinecraftRgb(Rgb([142, 60, 46]), 202),
-    MinecraftRgb(Rgb([75, 31, 24]), 203),
-    MinecraftRgb(Rgb([26, 15, 11]), 204),
-    MinecraftRgb(Rgb([31, 18, 13]), 205),
-    MinecraftRgb(Rgb([37, 22, 16]), 206),
-    MinecraftRgb(Rgb([19, 11, 8]), 207),
-    MinecraftRgb(Rgb([133, 33, 34]), 208),
-    MinecraftRgb(Rgb([163, 41, 42]), 209),
-    MinecraftRgb(Rgb([189, 48, 49]), 210),
-    MinecraftRgb(Rgb([100, 25, 25]), 211),
-    MinecraftRgb(Rgb([104, 44, 68]), 212),
-    MinecraftRgb(Rgb([127, 54, 83]), 213),
-    MinecraftRgb(Rgb([148, 63, 97]), 214),
-    MinecraftRgb(Rgb([78, 33, 51]), 215),
-    MinecraftRgb(Rgb([64, 17, 20]), 216),
-    MinecraftRgb(Rgb([79, 21, 25]), 217),
-    MinecraftRgb(Rgb([92, 25, 29]), 218),
-    MinecraftRgb(Rgb([48, 13, 15]), 219),
-    MinecraftRgb(Rgb([15, 88, 94]), 220),
-    MinecraftRgb(Rgb([18, 108, 115]), 221),
-    MinecraftRgb(Rgb([22, 126, 134]), 222),
-    MinecraftRgb(Rgb([11, 66, 70]), 223),
-    MinecraftRgb(Rgb([40, 100, 98]), 224),
-    MinecraftRgb(Rgb([50, 122, 120]), 225),
-    MinecraftRgb(Rgb([58, 142, 140]), 226),
-    MinecraftRgb(Rgb([30, 75, 74]), 227),
-    MinecraftRgb(Rgb([60, 31, 43]), 228),
-    MinecraftRgb(Rgb([74, 37, 53]), 229),
-    MinecraftRgb(Rgb([86, 44, 62]), 230),
-    MinecraftRgb(Rgb([45, 23, 32]), 231),
-    MinecraftRgb(Rgb([14, 127, 93]), 232),
-    MinecraftRgb(Rgb([17, 155, 114]), 233),
-    MinecraftRgb(Rgb([20, 180, 133]), 234),
-    MinecraftRgb(Rgb([10, 95, 70]), 235),
-    MinecraftRgb(Rgb([70, 70, 70]), 236),
-    MinecraftRgb(Rgb([86, 86, 86]), 237),
-    MinecraftRgb(Rgb([100, 100, 100]), 238),
-    MinecraftRgb(Rgb([52, 52, 52]), 239),
-    MinecraftRgb(Rgb([152, 123, 103]), 240),
-    MinecraftRgb(Rgb([186, 150, 126]), 241),
-    MinecraftRgb(Rgb([216, 175, 147]), 242),
-    MinecraftRgb(Rgb([114, 92, 77]), 243),
-    MinecraftRgb(Rgb([89, 117, 105]), 244),
-    MinecraftRgb(Rgb([109, 144, 129]), 245),
-    MinecraftRgb(Rgb([127, 167, 150]), 246),
-    MinecraftRgb(Rgb([67, 88, 79]), 247),
-];
+/// Returns every visible map color of a palette with the given number of base colors,
+/// i.e. every shade of every base color except the transparent one.
+fn palette(base_colors: usize, metric: ColorMetric) -> Vec<MinecraftRgb> {
+    BASE_COLORS[1..base_colors]
+        .iter()
+        .enumerate()
+        .flat_map(|(i, base)| {
+            SHADE_MULTIPLIERS
+                .iter()
+                .enumerate()
+                .map(move |(shade, multiplier)| {
+                    let color = base
+                        .0
+                        .map(|channel| (channel as u32 * multiplier / 255) as u8);
+                    let color = Rgb(color);
+                    MinecraftRgb(
+                        color,
+                        ((i + 1) * 4 + shade) as MapColor,
+                        metric.coordinates(&color),
+                    )
+                })
+        })
+        .collect()
+}
 
-/// Wrapper around KdTree3
-pub struct MinecraftColorTree(KdTree3<MinecraftRgb>);
+/// Finds the closest map color to a given color.
+/// Uses a kd-tree built in the space of the metric if it is Euclidean there,
+/// otherwise every color in the palette is compared.
+/// With a lookup table, the search is replaced by a single read from the table.
+pub struct MinecraftColorTree {
+    base_colors: usize,
+    metric: ColorMetric,
+    palette: Vec<MinecraftRgb>,
+    tree: KdTree3<MinecraftRgb>,
+    lookup_table: Option<ColorLookupTable>,
+}
 pub type MapColor = i8;
-pub type RgbDifference = [i16; 3];
 
 impl MinecraftColorTree {
-    /// Returns the closest color in the Minecraft color palette and the distance to it.
-    pub fn find_closest(&self, color: &Rgb<u8>) -> (MapColor, RgbDifference) {
-        // Check if black, don't propagate the error
+    /// Builds the tree for the palette of a Minecraft version with the given number of base colors.
+    pub fn new(base_colors: usize, metric: ColorMetric) -> Self {
+        let palette = palette(base_colors, metric);
+        MinecraftColorTree {
+            base_colors,
+            metric,
+            tree: KdTree3::build_by_ordered_float(palette.clone()),
+            palette,
+            lookup_table: None,
+        }
+    }
+
+    /// Makes the tree use a lookup table indexed with the given number of bits per channel,
+    /// loaded from the cache or built from the tree.
+    pub fn use_lookup_table(&mut self, bits: u32) -> anyhow::Result<()> {
+        self.lookup_table = None;
+        let lookup_table =
+            ColorLookupTable::load_or_build(self, bits, self.base_colors, self.metric)?;
+        self.lookup_table = Some(lookup_table);
+        Ok(())
+    }
+
+    /// Returns the number of colors in the palette, which have the map color IDs from 4 on.
+    pub fn palette_len(&self) -> usize {
+        self.palette.len()
+    }
+
+    /// Returns the closest color in the Minecraft color palette, and its RGB value to compute the error from.
+    pub fn find_closest(&self, color: &Rgb<u8>) -> (MapColor, Rgb<u8>) {
+        // Check if black, and treat it as an exact match so that no error is propagated
         if color == &Rgb([0, 0, 0]) {
-            return (BLACK_INDEX, [0, 0, 0]);
+            return (BLACK_INDEX, *color);
         }
 
-        // Cast to MinecraftRgb to use the KdTree, the index is ignored
-        let mc_rgb = MinecraftRgb(*color, 0);
-        let nearest = self.0.nearest(&mc_rgb).unwrap();
+        if let Some(lookup_table) = &self.lookup_table {
+            let index = lookup_table.get(color);
+            // Palette colors are ordered by map color ID, which starts after the 4 transparent ones
+            return (index, self.palette[index as u8 as usize - 4].0);
+        }
 
-        // KdTree returns the squared distance in `nearest`, but we want the actual distance
-        let distance = [
-            color.0[0] as i16 - nearest.item.0[0] as i16,
-            color.0[1] as i16 - nearest.item.0[1] as i16,
-            color.0[2] as i16 - nearest.item.0[2] as i16,
-        ];
+        // Cast to MinecraftRgb to use the KdTree, the index is ignored
+        let mc_rgb = MinecraftRgb(*color, 0, self.metric.coordinates(color));
+        let nearest = if self.metric.is_euclidean() {
+            self.tree.nearest(&mc_rgb).unwrap().item
+        } else {
+            self.palette
+                .iter()
+                .min_by(|a, b| {
+                    let distance_a = self.metric.distance(&mc_rgb.2, &a.2);
+                    let distance_b = self.metric.distance(&mc_rgb.2, &b.2);
+                    distance_a.total_cmp(&distance_b)
+                })
+                .unwrap()
+        };
 
-        // Return the MC index of the color and the distance
-        (nearest.item.1, distance)
+        // Return the MC index of the color and its RGB value
+        (nearest.1, nearest.0)
     }
 }
 
-lazy_static! {
-    pub static ref MINECRAFT_COLOR_TREE: MinecraftColorTree =
-        MinecraftColorTree(KdTree3::build(Vec::from(COLOR_LIST)));
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    /// The colors of the palette as they were listed by hand before it was generated,
+    /// for map color IDs 4 to 247 in order.
+    const LISTED_COLORS: [[u8; 3]; 244] = [
+        [89, 125, 39],
+        [109, 153, 48],
+        [127, 178, 56],
+        [67, 94, 29],
+        [174, 164, 115],
+        [213, 201, 140],
+        [247, 233, 163],
+        [130, 123, 86],
+        [140, 140, 140],
+        [171, 171, 171],
+        [199, 199, 199],
+        [105, 105, 105],
+        [180, 0, 0],
+        [220, 0, 0],
+        [255, 0, 0],
+        [135, 0, 0],
+        [112, 112, 180],
+        [138, 138, 220],
+        [160, 160, 255],
+        [84, 84, 135],
+        [117, 117, 117],
+        [144, 144, 144],
+        [167, 167, 167],
+        [88, 88, 88],
+        [0, 87, 0],
+        [0, 106, 0],
+        [0, 124, 0],
+        [0, 65, 0],
+        [180, 180, 180],
+        [220, 220, 220],
+        [255, 255, 255],
+        [135, 135, 135],
+        [115, 118, 129],
+        [141, 144, 158],
+        [164, 168, 184],
+        [86, 88, 97],
+        [106, 76, 54],
+        [130, 94, 66],
+        [151, 109, 77],
+        [79, 57, 40],
+        [79, 79, 79],
+        [96, 96, 96],
+        [112, 112, 112],
+        [59, 59, 59],
+        [45, 45, 180],
+        [55, 55, 220],
+        [64, 64, 255],
+        [33, 33, 135],
+        [100, 84, 50],
+        [123, 102, 62],
+        [143, 119, 72],
+        [75, 63, 38],
+        [180, 177, 172],
+        [220, 217, 211],
+        [255, 252, 245],
+        [135, 133, 129],
+        [152, 89, 36],
+        [186, 109, 44],
+        [216, 127, 51],
+        [114, 67, 27],
+        [125, 53, 152],
+        [153, 65, 186],
+        [178, 76, 216],
+        [94, 40, 114],
+        [72, 108, 152],
+        [88, 132, 186],
+        [102, 153, 216],
+        [54, 81, 114],
+        [161, 161, 36],
+        [197, 197, 44],
+        [229, 229, 51],
+        [121, 121, 27],
+        [89, 144, 17],
+        [109, 176, 21],
+        [127, 204, 25],
+        [67, 108, 13],
+        [170, 89, 116],
+        [208, 109, 142],
+        [242, 127, 165],
+        [128, 67, 87],
+        [53, 53, 53],
+        [65, 65, 65],
+        [76, 76, 76],
+        [40, 40, 40],
+        [108, 108, 108],
+        [132, 132, 132],
+        [153, 153, 153],
+        [81, 81, 81],
+        [53, 89, 108],
+        [65, 109, 132],
+        [76, 127, 153],
+        [40, 67, 81],
+        [89, 44, 125],
+        [109, 54, 153],
+        [127, 63, 178],
+        [67, 33, 94],
+        [36, 53, 125],
+        [44, 65, 153],
+        [51, 76, 178],
+        [27, 40, 94],
+        [72, 53, 36],
+        [88, 65, 44],
+        [102, 76, 51],
+        [54, 40, 27],
+        [72, 89, 36],
+        [88, 109, 44],
+        [102, 127, 51],
+        [54, 67, 27],
+        [108, 36, 36],
+        [132, 44, 44],
+        [153, 51, 51],
+        [81, 27, 27],
+        [17, 17, 17],
+        [21, 21, 21],
+        [25, 25, 25],
+        [13, 13, 13],
+        [176, 168, 54],
+        [215, 205, 66],
+        [250, 238, 77],
+        [132, 126, 40],
+        [64, 154, 150],
+        [79, 188, 183],
+        [92, 219, 213],
+        [48, 115, 112],
+        [52, 90, 180],
+        [63, 110, 220],
+        [74, 128, 255],
+        [39, 67, 135],
+        [0, 153, 40],
+        [0, 187, 50],
+        [0, 217, 58],
+        [0, 114, 30],
+        [91, 60, 34],
+        [111, 74, 42],
+        [129, 86, 49],
+        [68, 45, 25],
+        [79, 1, 0],
+        [96, 1, 0],
+        [112, 2, 0],
+        [59, 1, 0],
+        [147, 124, 113],
+        [180, 152, 138],
+        [209, 177, 161],
+        [110, 93, 85],
+        [112, 57, 25],
+        [137, 70, 31],
+        [159, 82, 36],
+        [84, 43, 19],
+        [105, 61, 76],
+        [128, 75, 93],
+        [149, 87, 108],
+        [78, 46, 57],
+        [79, 76, 97],
+        [96, 93, 119],
+        [112, 108, 138],
+        [59, 57, 73],
+        [131, 93, 25],
+        [160, 114, 31],
+        [186, 133, 36],
+        [98, 70, 19],
+        [72, 82, 37],
+        [88, 100, 45],
+        [103, 117, 53],
+        [54, 61, 28],
+        [112, 54, 55],
+        [138, 66, 67],
+        [160, 77, 78],
+        [84, 40, 41],
+        [40, 28, 24],
+        [49, 35, 30],
+        [57, 41, 35],
+        [30, 21, 18],
+        [95, 75, 69],
+        [116, 92, 84],
+        [135, 107, 98],
+        [71, 56, 51],
+        [61, 64, 64],
+        [75, 79, 79],
+        [87, 92, 92],
+        [46, 48, 48],
+        [86, 51, 62],
+        [105, 62, 75],
+        [122, 73, 88],
+        [64, 38, 46],
+        [53, 43, 64],
+        [65, 53, 79],
+        [76, 62, 92],
+        [40, 32, 48],
+        [53, 35, 24],
+        [65, 43, 30],
+        [76, 50, 35],
+        [40, 26, 18],
+        [53, 57, 29],
+        [65, 70, 36],
+        [76, 82, 42],
+        [40, 43, 22],
+        [100, 42, 32],
+        [122, 51, 39],
+        [142, 60, 46],
+        [75, 31, 24],
+        [26, 15, 11],
+        [31, 18, 13],
+        [37, 22, 16],
+        [19, 11, 8],
+        [133, 33, 34],
+        [163, 41, 42],
+        [189, 48, 49],
+        [100, 25, 25],
+        [104, 44, 68],
+        [127, 54, 83],
+        [148, 63, 97],
+        [78, 33, 51],
+        [64, 17, 20],
+        [79, 21, 25],
+        [92, 25, 29],
+        [48, 13, 15],
+        [15, 88, 94],
+        [18, 108, 115],
+        [22, 126, 134],
+        [11, 66, 70],
+        [40, 100, 98],
+        [50, 122, 120],
+        [58, 142, 140],
+        [30, 75, 74],
+        [60, 31, 43],
+        [74, 37, 53],
+        [86, 44, 62],
+        [45, 23, 32],
+        [14, 127, 93],
+        [17, 155, 114],
+        [20, 180, 133],
+        [10, 95, 70],
+        [70, 70, 70],
+        [86, 86, 86],
+        [100, 100, 100],
+        [52, 52, 52],
+        [152, 123, 103],
+        [186, 150, 126],
+        [216, 175, 147],
+        [114, 92, 77],
+        [89, 117, 105],
+        [109, 144, 129],
+        [127, 167, 150],
+        [67, 88, 79],
+    ];
+
+    #[test]
+    fn palette_matches_the_listed_colors() {
+        let palette = palette(BASE_COLORS_1_17, ColorMetric::Srgb);
+        assert_eq!(palette.len(), LISTED_COLORS.len());
+        for (i, (color, listed)) in palette.iter().zip(LISTED_COLORS).enumerate() {
+            assert_eq!(color.0 .0, listed, "color {}", i + 4);
+            assert_eq!(color.1 as u8 as usize, i + 4);
+        }
+    }
+
+    #[test]
+    fn black_index_is_the_darkest_shade_of_black() {
+        let palette = palette(BASE_COLORS_1_17, ColorMetric::Srgb);
+        assert_eq!(BLACK_INDEX, 119);
+        let black = palette[BLACK_INDEX as usize - 4];
+        assert_eq!(black.0, Rgb([13, 13, 13]));
+        assert_eq!(black.1, BLACK_INDEX);
+    }
+
+    #[test]
+    fn older_palettes_are_a_prefix_of_newer_ones() {
+        let old = palette(BASE_COLORS_1_16, ColorMetric::Srgb);
+        let new = palette(BASE_COLORS_1_17, ColorMetric::Srgb);
+        assert_eq!(old.len(), (BASE_COLORS_1_16 - 1) * 4);
+        for (old, new) in old.iter().zip(&new) {
+            assert_eq!((old.0, old.1), (new.0, new.1));
+        }
+    }
+
+    /// The pixels of a fixed 1024x768 image: smooth gradients with some noise on top,
+    /// so that neighbouring pixels are close but rarely the same color.
+    fn fixed_image() -> Vec<Rgb<u8>> {
+        let mut seed = 0x2545_f491_u32;
+        let mut noise = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed % 32
+        };
+        (0..768u32)
+            .flat_map(|y| (0..1024u32).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                Rgb([
+                    ((x / 4 + noise()) % 256) as u8,
+                    ((y / 3 + noise()) % 256) as u8,
+                    (((x + y) / 7 + noise()) % 256) as u8,
+                ])
+            })
+            .collect()
+    }
+
+    /// Times matching the fixed image with the kd-tree and both lookup tables.
+    /// Run with `cargo test --release bench_find_closest -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_find_closest() {
+        let image = fixed_image();
+        let matches = |tree: &MinecraftColorTree| {
+            image
+                .iter()
+                .map(|color| tree.find_closest(color).0 as i64)
+                .sum::<i64>()
+        };
+
+        for (name, bits) in [("kd-tree", None), ("reduced", Some(5)), ("full", Some(8))] {
+            let mut tree = MinecraftColorTree::new(BASE_COLORS_1_17, ColorMetric::Srgb);
+            // The table is built (or loaded from the cache) before timing, as it only is once
+            if let Some(bits) = bits {
+                tree.use_lookup_table(bits).unwrap();
+            }
+            let start = Instant::now();
+            let rounds = 10;
+            let checksum = (0..rounds).map(|_| matches(&tree)).sum::<i64>();
+            println!(
+                "{name}: {:.1} ms per 1024x768 image (checksum {checksum})",
+                start.elapsed().as_secs_f64() * 1000.0 / rounds as f64
+            );
+        }
+    }
 }
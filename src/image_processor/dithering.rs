@@ -0,0 +1,304 @@
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// An error diffusion kernel, as a list of offsets `[x, y]` from the current pixel
+/// and the fraction of the error that is propagated to the pixel at that offset.
+pub type Kernel = &'static [([i32; 2], f32)];
+
+/// The dithering algorithm used when converting colors.
+#[derive(Copy, Clone, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dithering {
+    /// No dithering, every pixel is matched to the closest color
+    None,
+    FloydSteinberg,
+    JarvisJudiceNinke,
+    Stucki,
+    /// Only propagates 3/4 of the error, which keeps more contrast
+    Atkinson,
+    Sierra,
+    TwoRowSierra,
+    SierraLite,
+    Burkes,
+    /// Ordered dithering with a 2x2 Bayer matrix.
+    /// Like the other ordered modes, the pattern only depends on the pixel position,
+    /// so it stays still between frames of an animation
+    Bayer2,
+    /// Ordered dithering with a 4x4 Bayer matrix
+    Bayer4,
+    /// Ordered dithering with an 8x8 Bayer matrix
+    Bayer8,
+    /// Ordered dithering with a 16x16 Bayer matrix
+    Bayer16,
+    /// Ordered dithering with a 64x64 blue noise texture, which has no visible pattern
+    BlueNoise,
+}
+
+/// How far ordered dithering moves a color, in 0-255 units, between the lowest and highest threshold.
+/// Roughly the distance between neighbouring colors in the palette.
+const ORDERED_SPREAD: f32 = 40.0;
+
+/// The size of the blue noise texture, which is tiled over the image.
+const BLUE_NOISE_SIZE: usize = 64;
+
+const FLOYD_STEINBERG: Kernel = &[
+    ([1, 0], 7.0 / 16.0),
+    ([-1, 1], 3.0 / 16.0),
+    ([0, 1], 5.0 / 16.0),
+    ([1, 1], 1.0 / 16.0),
+];
+
+const JARVIS_JUDICE_NINKE: Kernel = &[
+    ([1, 0], 7.0 / 48.0),
+    ([2, 0], 5.0 / 48.0),
+    ([-2, 1], 3.0 / 48.0),
+    ([-1, 1], 5.0 / 48.0),
+    ([0, 1], 7.0 / 48.0),
+    ([1, 1], 5.0 / 48.0),
+    ([2, 1], 3.0 / 48.0),
+    ([-2, 2], 1.0 / 48.0),
+    ([-1, 2], 3.0 / 48.0),
+    ([0, 2], 5.0 / 48.0),
+    ([1, 2], 3.0 / 48.0),
+    ([2, 2], 1.0 / 48.0),
+];
+
+const STUCKI: Kernel = &[
+    ([1, 0], 8.0 / 42.0),
+    ([2, 0], 4.0 / 42.0),
+    ([-2, 1], 2.0 / 42.0),
+    ([-1, 1], 4.0 / 42.0),
+    ([0, 1], 8.0 / 42.0),
+    ([1, 1], 4.0 / 42.0),
+    ([2, 1], 2.0 / 42.0),
+    ([-2, 2], 1.0 / 42.0),
+    ([-1, 2], 2.0 / 42.0),
+    ([0, 2], 4.0 / 42.0),
+    ([1, 2], 2.0 / 42.0),
+    ([2, 2], 1.0 / 42.0),
+];
+
+const ATKINSON: Kernel = &[
+    ([1, 0], 1.0 / 8.0),
+    ([2, 0], 1.0 / 8.0),
+    ([-1, 1], 1.0 / 8.0),
+    ([0, 1], 1.0 / 8.0),
+    ([1, 1], 1.0 / 8.0),
+    ([0, 2], 1.0 / 8.0),
+];
+
+const SIERRA: Kernel = &[
+    ([1, 0], 5.0 / 32.0),
+    ([2, 0], 3.0 / 32.0),
+    ([-2, 1], 2.0 / 32.0),
+    ([-1, 1], 4.0 / 32.0),
+    ([0, 1], 5.0 / 32.0),
+    ([1, 1], 4.0 / 32.0),
+    ([2, 1], 2.0 / 32.0),
+    ([-1, 2], 2.0 / 32.0),
+    ([0, 2], 3.0 / 32.0),
+    ([1, 2], 2.0 / 32.0),
+];
+
+const TWO_ROW_SIERRA: Kernel = &[
+    ([1, 0], 4.0 / 16.0),
+    ([2, 0], 3.0 / 16.0),
+    ([-2, 1], 1.0 / 16.0),
+    ([-1, 1], 2.0 / 16.0),
+    ([0, 1], 3.0 / 16.0),
+    ([1, 1], 2.0 / 16.0),
+    ([2, 1], 1.0 / 16.0),
+];
+
+const SIERRA_LITE: Kernel = &[
+    ([1, 0], 2.0 / 4.0),
+    ([-1, 1], 1.0 / 4.0),
+    ([0, 1], 1.0 / 4.0),
+];
+
+const BURKES: Kernel = &[
+    ([1, 0], 8.0 / 32.0),
+    ([2, 0], 4.0 / 32.0),
+    ([-2, 1], 2.0 / 32.0),
+    ([-1, 1], 4.0 / 32.0),
+    ([0, 1], 8.0 / 32.0),
+    ([1, 1], 4.0 / 32.0),
+    ([2, 1], 2.0 / 32.0),
+];
+
+impl Dithering {
+    /// Returns the error diffusion kernel of the algorithm, for a left to right scan.
+    /// Empty if the algorithm does not diffuse error.
+    pub fn kernel(self) -> Kernel {
+        match self {
+            Dithering::None
+            | Dithering::Bayer2
+            | Dithering::Bayer4
+            | Dithering::Bayer8
+            | Dithering::Bayer16
+            | Dithering::BlueNoise => &[],
+            Dithering::FloydSteinberg => FLOYD_STEINBERG,
+            Dithering::JarvisJudiceNinke => JARVIS_JUDICE_NINKE,
+            Dithering::Stucki => STUCKI,
+            Dithering::Atkinson => ATKINSON,
+            Dithering::Sierra => SIERRA,
+            Dithering::TwoRowSierra => TWO_ROW_SIERRA,
+            Dithering::SierraLite => SIERRA_LITE,
+            Dithering::Burkes => BURKES,
+        }
+    }
+
+    /// Returns the amount to add to each channel of the pixel at the given position before matching it
+    /// to the palette, for ordered dithering. `None` if the algorithm is not an ordered one.
+    pub fn offset(self, x: u32, y: u32) -> Option<f32> {
+        let (matrix, size) = match self {
+            Dithering::Bayer2 => (bayer_matrix(1), 2),
+            Dithering::Bayer4 => (bayer_matrix(2), 4),
+            Dithering::Bayer8 => (bayer_matrix(3), 8),
+            Dithering::Bayer16 => (bayer_matrix(4), 16),
+            Dithering::BlueNoise => (blue_noise(), BLUE_NOISE_SIZE),
+            _ => return None,
+        };
+        let rank = matrix[(y as usize % size) * size + x as usize % size];
+
+        // Center the thresholds around zero
+        let threshold = (rank as f32 + 0.5) / matrix.len() as f32 - 0.5;
+        Some(threshold * ORDERED_SPREAD)
+    }
+}
+
+/// Returns the Bayer matrix of size `2^order`, in row-major order.
+/// Each element is the rank of that position, from 0 to the number of elements minus one.
+fn bayer_matrix(order: usize) -> &'static [u32] {
+    static MATRICES: OnceLock<Vec<Vec<u32>>> = OnceLock::new();
+    let matrices = MATRICES.get_or_init(|| {
+        // Each matrix is built from the previous one, as
+        // [4M + 0, 4M + 2]
+        // [4M + 3, 4M + 1]
+        let mut matrices = vec![vec![0]];
+        for order in 1..=4 {
+            let previous: &Vec<u32> = &matrices[order - 1];
+            let half = 1 << (order - 1);
+            let size = half * 2;
+            let mut matrix = vec![0; size * size];
+            for y in 0..size {
+                for x in 0..size {
+                    let quadrant = match (x / half, y / half) {
+                        (0, 0) => 0,
+                        (1, 0) => 2,
+                        (0, 1) => 3,
+                        _ => 1,
+                    };
+                    matrix[y * size + x] = 4 * previous[(y % half) * half + x % half] + quadrant;
+                }
+            }
+            matrices.push(matrix);
+        }
+        matrices
+    });
+    &matrices[order]
+}
+
+/// Returns a blue noise texture of size `BLUE_NOISE_SIZE`, in row-major order,
+/// with each element being the rank of that position as in `bayer_matrix`.
+///
+/// The texture is generated once with the void-and-cluster method: starting from a sparse random
+/// pattern, points are ranked by repeatedly removing the most tightly clustered point,
+/// then adding a point in the largest void until every position is ranked.
+/// A fixed seed is used so the texture is the same on every run.
+fn blue_noise() -> &'static [u32] {
+    static TEXTURE: OnceLock<Vec<u32>> = OnceLock::new();
+    TEXTURE.get_or_init(|| {
+        const SIZE: usize = BLUE_NOISE_SIZE;
+        const SIGMA: f32 = 1.5;
+
+        // The Gaussian weight of every toroidal offset
+        let mut weights = vec![0.0; SIZE * SIZE];
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let dx = x.min(SIZE - x) as f32;
+                let dy = y.min(SIZE - y) as f32;
+                weights[y * SIZE + x] = (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp();
+            }
+        }
+
+        // Adds (or removes) a point's contribution to the energy of every position
+        let update = |energy: &mut [f32], point: usize, sign: f32| {
+            let (px, py) = (point % SIZE, point / SIZE);
+            for y in 0..SIZE {
+                for x in 0..SIZE {
+                    let offset = ((y + SIZE - py) % SIZE) * SIZE + (x + SIZE - px) % SIZE;
+                    energy[y * SIZE + x] += sign * weights[offset];
+                }
+            }
+        };
+        // The point with the highest energy, i.e. the tightest cluster
+        let tightest_cluster = |energy: &[f32], points: &[bool]| {
+            (0..SIZE * SIZE)
+                .filter(|&i| points[i])
+                .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+                .unwrap()
+        };
+        // The empty position with the lowest energy, i.e. the largest void
+        let largest_void = |energy: &[f32], points: &[bool]| {
+            (0..SIZE * SIZE)
+                .filter(|&i| !points[i])
+                .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+                .unwrap()
+        };
+
+        // A sparse random initial pattern, from a xorshift generator with a fixed seed
+        let mut points = vec![false; SIZE * SIZE];
+        let mut energy = vec![0.0; SIZE * SIZE];
+        let mut state: u32 = 0x9e37_79b9;
+        let mut initial = 0;
+        while initial < SIZE * SIZE / 10 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let point = state as usize % (SIZE * SIZE);
+            if !points[point] {
+                points[point] = true;
+                update(&mut energy, point, 1.0);
+                initial += 1;
+            }
+        }
+
+        // Spread the initial points out evenly, by moving the tightest cluster into the largest void
+        // until that no longer changes anything
+        loop {
+            let cluster = tightest_cluster(&energy, &points);
+            points[cluster] = false;
+            update(&mut energy, cluster, -1.0);
+            let void = largest_void(&energy, &points);
+            points[void] = true;
+            update(&mut energy, void, 1.0);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut ranks = vec![0; SIZE * SIZE];
+
+        // Rank the initial points from the last to the first, by removing the tightest cluster
+        let (mut removed_points, mut removed_energy) = (points.clone(), energy.clone());
+        for rank in (0..initial).rev() {
+            let cluster = tightest_cluster(&removed_energy, &removed_points);
+            removed_points[cluster] = false;
+            update(&mut removed_energy, cluster, -1.0);
+            ranks[cluster] = rank as u32;
+        }
+
+        // Rank the remaining positions by filling the largest void
+        for rank in initial..SIZE * SIZE {
+            let void = largest_void(&energy, &points);
+            points[void] = true;
+            update(&mut energy, void, 1.0);
+            ranks[void] = rank as u32;
+        }
+
+        ranks
+    })
+}
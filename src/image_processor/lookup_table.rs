@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::ValueEnum;
+use image::Rgb;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::image_processor::colors::{MapColor, MinecraftColorTree};
+use crate::image_processor::metric::ColorMetric;
+
+/// Whether colors are matched through a precomputed table covering every possible color.
+#[derive(Copy, Clone, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LookupTable {
+    /// Search the palette for every pixel
+    None,
+    /// 2^15 entries (32 KiB), using the 5 most significant bits of each channel. Slightly less accurate
+    Reduced,
+    /// 2^24 entries (16 MiB), one for every color. Exact, but slow to build the first time
+    Full,
+}
+
+impl LookupTable {
+    /// Returns the number of bits per channel used to index the table, `None` if there is no table.
+    pub fn bits(self) -> Option<u32> {
+        match self {
+            LookupTable::None => None,
+            LookupTable::Reduced => Some(5),
+            LookupTable::Full => Some(8),
+        }
+    }
+}
+
+/// A table of the closest map color of every color, indexed by the most significant bits of each channel.
+/// Building it takes one search per entry, so it is cached on disk for each palette and metric.
+pub struct ColorLookupTable {
+    bits: u32,
+    table: Vec<MapColor>,
+}
+
+impl ColorLookupTable {
+    /// Loads the table for the given palette and metric from the cache,
+    /// or builds it with the given tree and saves it to the cache.
+    pub fn load_or_build(
+        tree: &MinecraftColorTree,
+        bits: u32,
+        base_colors: usize,
+        metric: ColorMetric,
+    ) -> anyhow::Result<Self> {
+        let path = cache_path(bits, base_colors, metric);
+        let size = 1 << (bits * 3);
+
+        // A cache file of the wrong size or with colors outside the palette was cut short or corrupted,
+        // so it is rebuilt
+        let palette = 4..4 + tree.palette_len();
+        if let Ok(bytes) = fs::read(&path) {
+            if bytes.len() == size && bytes.iter().all(|&byte| palette.contains(&(byte as usize))) {
+                let table = bytes.into_iter().map(|byte| byte as MapColor).collect();
+                return Ok(ColorLookupTable { bits, table });
+            }
+        }
+
+        println!("Building color lookup table, this only happens once...");
+        let table = (0..size)
+            .into_par_iter()
+            .map(|index| tree.find_closest(&bucket_color(index, bits)).0)
+            .collect::<Vec<MapColor>>();
+
+        fs::create_dir_all(path.parent().unwrap())
+            .with_context(|| "failed to create the lookup table cache directory")?;
+        let bytes = table.iter().map(|&color| color as u8).collect::<Vec<u8>>();
+        // Written to a temporary file first, so that another run never reads a partly written table
+        let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temp_path, bytes)
+            .with_context(|| format!("failed to write {}", temp_path.display()))?;
+        fs::rename(&temp_path, &path)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+
+        Ok(ColorLookupTable { bits, table })
+    }
+
+    /// Returns the closest map color to the given color.
+    pub fn get(&self, color: &Rgb<u8>) -> MapColor {
+        let shift = 8 - self.bits;
+        let [r, g, b] = color.0.map(|channel| (channel >> shift) as usize);
+        self.table[(r << (self.bits * 2)) | (g << self.bits) | b]
+    }
+}
+
+/// Returns the color that the table entry at `index` is computed for,
+/// which is the center of the range of colors that share the entry.
+fn bucket_color(index: usize, bits: u32) -> Rgb<u8> {
+    let shift = 8 - bits;
+    let mask = (1 << bits) - 1;
+    let center = (1 << shift) >> 1;
+    let channel = |offset: u32| ((((index >> offset) & mask) << shift) | center) as u8;
+    Rgb([channel(bits * 2), channel(bits), channel(0)])
+}
+
+/// The version of the cached tables, to be increased whenever the palette, a metric
+/// or the way a table is built changes, so that tables built before are not reused.
+const CACHE_VERSION: u32 = 1;
+
+/// Returns the path of the cached table, in the temporary directory of the system.
+/// The palette is identified by its number of base colors, as newer versions only ever add colors.
+fn cache_path(bits: u32, base_colors: usize, metric: ColorMetric) -> PathBuf {
+    let metric = metric.to_possible_value().unwrap();
+    std::env::temp_dir().join("mapmaker").join(format!(
+        "lut-v{CACHE_VERSION}-{}-{}-{}bit.bin",
+        metric.get_name(),
+        base_colors,
+        bits * 3
+    ))
+}
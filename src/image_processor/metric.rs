@@ -0,0 +1,167 @@
+use clap::ValueEnum;
+use image::Rgb;
+use serde::{Deserialize, Serialize};
+
+/// How the distance between two colors is measured when finding the closest map color.
+#[derive(Copy, Clone, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorMetric {
+    /// Euclidean distance between gamma-encoded sRGB values
+    Srgb,
+    /// Euclidean distance between linear-light RGB values
+    LinearRgb,
+    /// sRGB distance weighted by the mean red value, a cheap approximation of perceived difference
+    Redmean,
+    /// Euclidean distance in CIELAB, i.e. CIE76 delta E
+    Cielab,
+    /// CIEDE2000 delta E in CIELAB, the most accurate but slowest metric
+    Ciede2000,
+    /// Euclidean distance in Oklab
+    Oklab,
+}
+
+impl ColorMetric {
+    /// Converts an sRGB color into the coordinates used by this metric.
+    pub fn coordinates(self, color: &Rgb<u8>) -> [f32; 3] {
+        match self {
+            ColorMetric::Srgb | ColorMetric::Redmean => color.0.map(|channel| channel as f32),
+            ColorMetric::LinearRgb => color.0.map(srgb_to_linear),
+            ColorMetric::Cielab | ColorMetric::Ciede2000 => srgb_to_lab(color),
+            ColorMetric::Oklab => srgb_to_oklab(color),
+        }
+    }
+
+    /// Whether the distance is the Euclidean distance between coordinates,
+    /// which allows searching for the closest color with a kd-tree.
+    pub fn is_euclidean(self) -> bool {
+        !matches!(self, ColorMetric::Redmean | ColorMetric::Ciede2000)
+    }
+
+    /// Returns a value that increases with the distance between two colors, given as coordinates.
+    pub fn distance(self, a: &[f32; 3], b: &[f32; 3]) -> f32 {
+        match self {
+            ColorMetric::Redmean => {
+                let red_mean = (a[0] + b[0]) / 2.0;
+                let [dr, dg, db] = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+                (2.0 + red_mean / 256.0) * dr * dr
+                    + 4.0 * dg * dg
+                    + (2.0 + (255.0 - red_mean) / 256.0) * db * db
+            }
+            ColorMetric::Ciede2000 => ciede2000(a, b),
+            _ => a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum(),
+        }
+    }
+}
+
+/// Decodes a gamma-encoded sRGB channel into linear light, from 0 to 1.
+pub fn srgb_to_linear(channel: u8) -> f32 {
+    let c = channel as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear light value from 0 to 1 into a gamma-encoded sRGB channel, from 0 to 255.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    let c = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    c * 255.0
+}
+
+/// Converts sRGB to CIELAB, relative to the D65 white point.
+fn srgb_to_lab(color: &Rgb<u8>) -> [f32; 3] {
+    let [r, g, b] = color.0.map(srgb_to_linear);
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Converts sRGB to Oklab.
+fn srgb_to_oklab(color: &Rgb<u8>) -> [f32; 3] {
+    let [r, g, b] = color.0.map(srgb_to_linear);
+    let l = (0.4122215 * r + 0.5363325 * g + 0.0514460 * b).cbrt();
+    let m = (0.2119035 * r + 0.6806995 * g + 0.107397 * b).cbrt();
+    let s = (0.0883025 * r + 0.2817188 * g + 0.6299787 * b).cbrt();
+    [
+        0.2104542 * l + 0.7936178 * m - 0.0040720 * s,
+        1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+        0.0259040 * l + 0.7827718 * m - 0.8086758 * s,
+    ]
+}
+
+/// Returns the CIEDE2000 color difference between two CIELAB colors.
+fn ciede2000(lab1: &[f32; 3], lab2: &[f32; 3]) -> f32 {
+    let [l1, a1, b1] = *lab1;
+    let [l2, a2, b2] = *lab2;
+
+    let c_mean = ((a1 * a1 + b1 * b1).sqrt() + (a2 * a2 + b2 * b2).sqrt()) / 2.0;
+    let c_mean7 = c_mean.powi(7);
+    let g = 0.5 * (1.0 - (c_mean7 / (c_mean7 + 25.0_f32.powi(7))).sqrt());
+    let (a1, a2) = (a1 * (1.0 + g), a2 * (1.0 + g));
+
+    let (c1, c2) = ((a1 * a1 + b1 * b1).sqrt(), (a2 * a2 + b2 * b2).sqrt());
+    let hue = |b: f32, a: f32| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.0)
+        }
+    };
+    let (h1, h2) = (hue(b1, a1), hue(b2, a2));
+
+    let dl = l2 - l1;
+    let dc = c2 - c1;
+    let dh = if c1 * c2 == 0.0 {
+        0.0
+    } else if (h2 - h1).abs() <= 180.0 {
+        h2 - h1
+    } else if h2 <= h1 {
+        h2 - h1 + 360.0
+    } else {
+        h2 - h1 - 360.0
+    };
+    let dh = 2.0 * (c1 * c2).sqrt() * (dh / 2.0).to_radians().sin();
+
+    let l_mean = (l1 + l2) / 2.0;
+    let c_mean = (c1 + c2) / 2.0;
+    let h_mean = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+
+    let t = 1.0 - 0.17 * (h_mean - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * h_mean).to_radians().cos()
+        + 0.32 * (3.0 * h_mean + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * h_mean - 63.0).to_radians().cos();
+    let d_theta = 30.0 * (-((h_mean - 275.0) / 25.0).powi(2)).exp();
+    let c_mean7 = c_mean.powi(7);
+    let rc = 2.0 * (c_mean7 / (c_mean7 + 25.0_f32.powi(7))).sqrt();
+    let l_offset = (l_mean - 50.0).powi(2);
+    let sl = 1.0 + 0.015 * l_offset / (20.0 + l_offset).sqrt();
+    let sc = 1.0 + 0.045 * c_mean;
+    let sh = 1.0 + 0.015 * c_mean * t;
+    let rt = -(2.0 * d_theta).to_radians().sin() * rc;
+
+    let (dl, dc, dh) = (dl / sl, dc / sc, dh / sh);
+    (dl * dl + dc * dc + dh * dh + rt * dc * dh).sqrt()
+}
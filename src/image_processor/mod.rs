@@ -1,11 +1,26 @@
-use std::path::Path;
+use image::imageops::{replace, resize};
+use image::{Rgb, Rgba, RgbaImage};
+use rayon::prelude::*;
 
-use image::imageops::overlay;
-use image::{GenericImageView, RgbImage};
-
-use crate::image_processor::colors::{MapColor, BLACK_INDEX, MINECRAFT_COLOR_TREE};
+use crate::cli::constants::McVersion;
+use crate::image_processor::colors::{
+    MapColor, MinecraftColorTree, BLACK_INDEX, TRANSPARENT_INDEX,
+};
+use crate::image_processor::dithering::Dithering;
+use crate::image_processor::lookup_table::LookupTable;
+use crate::image_processor::metric::{linear_to_srgb, srgb_to_linear, ColorMetric};
+use crate::image_processor::preprocess::Preprocessing;
+use crate::image_processor::resize::{Anchor, Fit, ResizeFilter};
+use crate::image_processor::tone::ToneAdjustments;
+use crate::input::Frame;
 
 pub mod colors;
+pub mod dithering;
+pub mod lookup_table;
+pub mod metric;
+pub mod preprocess;
+pub mod resize;
+pub mod tone;
 
 macro_rules! ceil_div {
     ($a:expr, $b:expr) => {
@@ -13,9 +28,54 @@ macro_rules! ceil_div {
     };
 }
 
-// For the Floyd-Steinberg dithering algorithm
-const DITHERING_VECTORS: [[i32; 2]; 4] = [[1, 0], [-1, 1], [0, 1], [1, 1]];
-const DITHERING_FACTORS: [f32; 4] = [0.4375, 0.1875, 0.3125, 0.0625];
+/// Options that control how images are converted to maps.
+pub struct ProcessorOptions {
+    /// Pixels with an alpha value below this are transparent on the map.
+    pub alpha_threshold: u8,
+
+    /// Whether the border added to fit the image on whole maps is transparent instead of black.
+    pub transparent_padding: bool,
+
+    /// The dithering algorithm.
+    pub dithering: Dithering,
+
+    /// Whether to scan every other row right to left when dithering,
+    /// which avoids the diagonal artifacts of always diffusing error the same way.
+    pub serpentine: bool,
+
+    /// How the distance between colors is measured when matching them to the palette.
+    pub metric: ColorMetric,
+
+    /// Whether dithering works in linear light instead of gamma-encoded sRGB,
+    /// which keeps dithered midtones from getting darker.
+    pub linear_light: bool,
+
+    /// Whether colors are matched through a precomputed table.
+    pub lookup_table: LookupTable,
+
+    /// The crop, rotation and flips applied to every frame.
+    pub preprocessing: Preprocessing,
+
+    /// The brightness, contrast, saturation, gamma and levels adjustments applied to every frame.
+    pub tone: ToneAdjustments,
+
+    /// The number of maps horizontally, if set.
+    /// Otherwise it follows from the other dimension and the aspect ratio of the image,
+    /// or from the width of the image if neither is set.
+    pub map_columns: Option<u32>,
+
+    /// The number of maps vertically, if set. Like `map_columns`.
+    pub map_rows: Option<u32>,
+
+    /// How the image is fitted onto the maps.
+    pub fit: Fit,
+
+    /// The resampling filter used when scaling the image.
+    pub filter: ResizeFilter,
+
+    /// Where the image is placed on the maps.
+    pub anchor: Anchor,
+}
 
 /// The image processor struct.
 pub struct Processor {
@@ -25,68 +85,185 @@ pub struct Processor {
     /// The height of the source image.
     height: u32,
 
-    /// The number of maps needed horizontally to render the source image.
-    /// Equal to `width / 128` rounded up.
+    /// The number of maps horizontally.
     pub map_columns: u32,
 
-    /// The width in pixels of all maps needed to render the source image.
+    /// The width in pixels of all maps.
     /// Equal to `map_columns * 128`.
     map_width: u32,
 
-    /// The number of maps needed vertically to render the source image.
-    /// Equal to `height / 128` rounded up.
+    /// The number of maps vertically.
     pub map_rows: u32,
 
-    /// The height in pixels of all maps needed to render the source image.
+    /// The height in pixels of all maps.
     /// Equal to `map_rows * 128`.
     map_height: u32,
+
+    /// The palette of the target Minecraft version.
+    colors: MinecraftColorTree,
+
+    options: ProcessorOptions,
 }
 
 impl Processor {
-    pub fn new(first_image: &Path) -> anyhow::Result<Self> {
-        let image = image::open(first_image)?;
+    pub fn new(
+        first_frame: &Frame,
+        version: &McVersion,
+        options: ProcessorOptions,
+    ) -> anyhow::Result<Self> {
+        let image = first_frame.open()?;
         let (width, height) = image.dimensions();
+
+        // The number of maps follows the size of the image after cropping and rotating it
+        let (edited_width, edited_height) = options.preprocessing.size((width, height))?;
+        // Computed in 64 bits, as a large number of maps times the other side of the image overflows 32 bits
+        let (edited_width, edited_height) = (u64::from(edited_width), u64::from(edited_height));
+        let (map_columns, map_rows) = match (options.map_columns, options.map_rows) {
+            (Some(columns), Some(rows)) => (u64::from(columns), u64::from(rows)),
+            (Some(columns), None) => (
+                u64::from(columns),
+                ceil_div!(edited_height * u64::from(columns), edited_width).max(1),
+            ),
+            (None, Some(rows)) => (
+                ceil_div!(edited_width * u64::from(rows), edited_height).max(1),
+                u64::from(rows),
+            ),
+            (None, None) => (ceil_div!(edited_width, 128), ceil_div!(edited_height, 128)),
+        };
+        if map_columns == 0 || map_rows == 0 {
+            anyhow::bail!("the maps must be at least 1x1");
+        }
+        // The maps are drawn from a single image covering all of them, whose size is limited to 32 bits
+        let pixels = map_columns
+            .checked_mul(map_rows)
+            .and_then(|maps| maps.checked_mul(128 * 128));
+        if pixels.is_none_or(|pixels| pixels > u64::from(u32::MAX)) {
+            anyhow::bail!("{map_columns}x{map_rows} maps are too many to convert at once");
+        }
+        let (map_columns, map_rows) = (map_columns as u32, map_rows as u32);
+
+        let mut colors = MinecraftColorTree::new(version.base_colors, options.metric);
+        if let Some(bits) = options.lookup_table.bits() {
+            colors.use_lookup_table(bits)?;
+        }
+
         Ok(Processor {
             width,
             height,
-            map_columns: ceil_div!(width, 128),
-            map_width: ceil_div!(width, 128) * 128,
-            map_rows: ceil_div!(height, 128),
-            map_height: ceil_div!(height, 128) * 128,
+            map_columns,
+            map_width: map_columns * 128,
+            map_rows,
+            map_height: map_rows * 128,
+            colors,
+            options,
         })
     }
 
-    /// Processes the given image file by resizing it to fit on a multiple of Minecraft maps.
+    /// Measures the levels over every frame of the sequence, if auto-levels is enabled.
+    pub fn measure_levels(&mut self, frames: &[Frame]) -> anyhow::Result<()> {
+        if !self.options.tone.auto_levels {
+            return Ok(());
+        }
+
+        let histogram = frames
+            .par_iter()
+            .map(|frame| {
+                let image = self.open_frame(frame)?;
+                anyhow::Ok(ToneAdjustments::histogram(
+                    &image,
+                    self.options.alpha_threshold,
+                ))
+            })
+            .try_reduce(
+                || [0; 256],
+                |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                    Ok(a)
+                },
+            )?;
+        self.options.tone.set_levels(&histogram);
+        Ok(())
+    }
+
+    /// Opens the image of the given frame and applies the crop, rotation and flips to it.
     /// Errors if the image dimensions do not match the dimensions of the `Processor`.
-    pub fn process_file(&self, source: &Path) -> anyhow::Result<RgbImage> {
-        let image = image::open(source)?.to_rgb8();
+    fn open_frame(&self, frame: &Frame) -> anyhow::Result<RgbaImage> {
+        let image = frame.open()?;
         let (width, height) = image.dimensions();
 
         if width != self.width || height != self.height {
-            anyhow::bail!("image dimensions do not match");
+            anyhow::bail!(
+                "{frame} is {width}x{height}, but the first frame is {}x{}; every frame must be the same size",
+                self.width,
+                self.height
+            );
         }
 
+        self.options.preprocessing.apply(image)
+    }
+
+    /// Processes the image of the given frame by editing it, then scaling and placing it onto the maps,
+    /// according to the fit and anchor, then adjusting its tone.
+    /// Errors if the image dimensions do not match the dimensions of the `Processor`.
+    pub fn process_frame(&self, frame: &Frame) -> anyhow::Result<RgbaImage> {
+        let image = self.open_frame(frame)?;
+        let (width, height) = image.dimensions();
+
+        let map_size = (self.map_width, self.map_height);
+        let size = self.options.fit.scaled_size((width, height), map_size);
+        let image = if size == (width, height) {
+            image
+        } else {
+            resize(&image, size.0, size.1, self.options.filter.into())
+        };
+        let image = self.options.tone.apply(image);
+
         // Create a new image with the dimensions that are a multiple of 128
-        let mut map = RgbImage::new(self.map_width, self.map_height);
+        let padding = if self.options.transparent_padding {
+            Rgba([0, 0, 0, 0])
+        } else {
+            Rgba([0, 0, 0, 255])
+        };
+        let mut map = RgbaImage::from_pixel(self.map_width, self.map_height, padding);
 
-        // Overlay the source image onto the new image, centered
-        overlay(
-            &mut map,
-            &image,
-            (self.map_width - self.width) as i64 / 2,
-            (self.map_height - self.height) as i64 / 2,
-        );
+        // Copy the source image onto the new image at the anchor, keeping its alpha channel.
+        // Parts of the image outside of the maps are cropped
+        let (x, y) = self.options.anchor.position(size, map_size);
+        replace(&mut map, &image, x, y);
 
         Ok(map)
     }
 
     /// Converts the colors in the given image to the closest Minecraft map color.
     /// Returns a vector of Minecraft map colors split by map
-    pub fn convert_colors(&self, mut image: RgbImage) -> Vec<[MapColor; 16384]> {
+    /// Transparent pixels are not dithered, and no error is propagated into them.
+    pub fn convert_colors(&self, image: RgbaImage) -> Vec<[MapColor; 16384]> {
         let mut result = vec![[BLACK_INDEX; 16384]; (self.map_columns * self.map_rows) as usize];
+        let (width, height) = image.dimensions();
+        let linear = self.options.linear_light;
+
+        // The image with full precision, which dithering adds the error to.
+        // Either in linear light (0 to 1) or gamma-encoded sRGB (0 to 255).
+        let mut buffer = image
+            .pixels()
+            .map(|pixel| {
+                let rgb = [pixel[0], pixel[1], pixel[2]];
+                if linear {
+                    rgb.map(srgb_to_linear)
+                } else {
+                    rgb.map(|channel| channel as f32)
+                }
+            })
+            .collect::<Vec<[f32; 3]>>();
+        let is_transparent =
+            |x: u32, y: u32| image.get_pixel(x, y)[3] < self.options.alpha_threshold;
 
-        for y in 0..image.height() {
-            for x in 0..image.width() {
+        let kernel = self.options.dithering.kernel();
+        for y in 0..height {
+            // Serpentine scanning goes right to left on odd rows, mirroring the kernel
+            let reverse = self.options.serpentine && y % 2 == 1;
+            for i in 0..width {
+                let x = if reverse { width - 1 - i } else { i };
                 // map relates to the index of the map across all maps
                 // map_px relates to the index of the pixel within a single map
                 let map_x = x / 128;
@@ -96,38 +273,68 @@ impl Processor {
                 let map_idx = (map_y * self.map_columns + map_x) as usize;
                 let map_px_idx = (map_px_y * 128 + map_px_x) as usize;
 
-                let color = image.get_pixel(x, y);
-                let (mc_idx, difference) = MINECRAFT_COLOR_TREE.find_closest(color);
-
-                // Continue if no error to propagate
-                if difference == [0, 0, 0] {
+                if is_transparent(x, y) {
+                    result[map_idx][map_px_idx] = TRANSPARENT_INDEX;
                     continue;
                 }
 
+                // The color this pixel should have, limited to colors that can be displayed
+                let target = if linear {
+                    buffer[(y * width + x) as usize].map(|value| value.clamp(0.0, 1.0))
+                } else {
+                    buffer[(y * width + x) as usize].map(|value| value.clamp(0.0, 255.0))
+                };
+                let mut color = Rgb(if linear {
+                    target.map(|value| linear_to_srgb(value).round() as u8)
+                } else {
+                    target.map(|value| value.round() as u8)
+                });
+
+                // Ordered dithering shifts the color by an amount that only depends on the position
+                if let Some(offset) = self.options.dithering.offset(x, y) {
+                    for channel in color.0.iter_mut() {
+                        *channel = (*channel as f32 + offset).clamp(0.0, 255.0) as u8;
+                    }
+                }
+
+                let (mc_idx, matched) = self.colors.find_closest(&color);
                 result[map_idx][map_px_idx] = mc_idx;
 
-                let errors = difference.map(|err| err as f32 / 256.0);
+                if kernel.is_empty() {
+                    continue;
+                }
+                let matched = if linear {
+                    matched.0.map(srgb_to_linear)
+                } else {
+                    matched.0.map(|channel| channel as f32)
+                };
+                let errors = [
+                    target[0] - matched[0],
+                    target[1] - matched[1],
+                    target[2] - matched[2],
+                ];
+
+                // Continue if no error to propagate
+                if errors == [0.0, 0.0, 0.0] {
+                    continue;
+                }
 
-                // Propagate errors to each of the four pixels according to Floyd-Steinberg
-                for ([vx, vy], factor) in DITHERING_VECTORS.iter().zip(DITHERING_FACTORS) {
-                    let x = x as i32 + *vx;
-                    let y = y as i32 + *vy;
+                // Propagate errors to the neighbouring pixels according to the kernel
+                for &([vx, vy], factor) in kernel {
+                    let x = x as i32 + if reverse { -vx } else { vx };
+                    let y = y as i32 + vy;
 
                     // Check bounds within image (y will never be negative)
-                    if x < 0 || x as u32 >= image.width() || y as u32 >= image.height() {
+                    if x < 0 || x as u32 >= width || y as u32 >= height {
+                        continue;
+                    }
+                    if is_transparent(x as u32, y as u32) {
                         continue;
                     }
 
-                    let original_color = image.get_pixel_mut(x as u32, y as u32);
-                    for (channel, error) in original_color.0.iter_mut().zip(errors) {
-                        let value = *channel as f32 / 256.0 + error * factor;
-                        if value >= 1.0 {
-                            *channel = 255;
-                        } else if value <= 0.0 {
-                            *channel = 0;
-                        } else {
-                            *channel = (value * 256.0) as u8;
-                        }
+                    let original_color = &mut buffer[(y as u32 * width + x as u32) as usize];
+                    for (channel, error) in original_color.iter_mut().zip(errors) {
+                        *channel += error * factor;
                     }
                 }
             }
@@ -0,0 +1,150 @@
+use std::str::FromStr;
+
+use image::imageops::{crop_imm, flip_horizontal, flip_vertical, rotate180, rotate270, rotate90};
+use image::RgbaImage;
+
+/// A length along one side of the image, in pixels or as a percentage of that side.
+#[derive(Copy, Clone)]
+pub enum Length {
+    Pixels(u32),
+    Percent(f32),
+}
+
+impl Length {
+    /// Returns the length in pixels, for a side of the given length.
+    fn resolve(self, side: u32) -> u32 {
+        match self {
+            Length::Pixels(pixels) => pixels,
+            Length::Percent(percent) => (side as f32 * percent / 100.0).round() as u32,
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_suffix('%') {
+            Some(percent) => {
+                let percent = percent.trim().parse::<f32>().map_err(|e| e.to_string())?;
+                if !(0.0..=100.0).contains(&percent) {
+                    return Err(format!("{s} is not a percentage between 0% and 100%"));
+                }
+                Ok(Length::Percent(percent))
+            }
+            None => s.parse().map(Length::Pixels).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// A crop rectangle, given as `x,y,width,height` where each value is in pixels or a percentage, e.g. `0,10%,100%,80%`.
+#[derive(Copy, Clone)]
+pub struct Crop {
+    x: Length,
+    y: Length,
+    width: Length,
+    height: Length,
+}
+
+impl Crop {
+    /// Returns the rectangle in pixels as `(x, y, width, height)`, for an image of the given size.
+    /// Errors if the rectangle is empty or not inside of the image.
+    fn resolve(self, (width, height): (u32, u32)) -> anyhow::Result<(u32, u32, u32, u32)> {
+        let x = self.x.resolve(width);
+        let y = self.y.resolve(height);
+        let crop_width = self.width.resolve(width);
+        let crop_height = self.height.resolve(height);
+
+        if crop_width == 0 || crop_height == 0 {
+            anyhow::bail!("the crop rectangle is empty");
+        }
+        if x.checked_add(crop_width).is_none_or(|right| right > width)
+            || y.checked_add(crop_height)
+                .is_none_or(|bottom| bottom > height)
+        {
+            anyhow::bail!(
+                "the crop rectangle {crop_width}x{crop_height} at {x},{y} is outside of the {width}x{height} image"
+            );
+        }
+        Ok((x, y, crop_width, crop_height))
+    }
+}
+
+impl FromStr for Crop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lengths = s
+            .split(',')
+            .map(str::parse::<Length>)
+            .collect::<Result<Vec<_>, _>>()?;
+        match lengths[..] {
+            [x, y, width, height] => Ok(Crop {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err("expected the crop rectangle as x,y,width,height".to_string()),
+        }
+    }
+}
+
+/// Edits applied to every frame before it is fitted onto the maps,
+/// in order: crop, rotate clockwise, then flip.
+pub struct Preprocessing {
+    pub crop: Option<Crop>,
+
+    /// Clockwise rotation in degrees, one of 0, 90, 180 or 270.
+    pub rotation: u32,
+
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl Preprocessing {
+    /// Returns the size of an image of the given size after preprocessing.
+    /// Errors if the edits cannot be applied to an image of that size.
+    pub fn size(&self, size: (u32, u32)) -> anyhow::Result<(u32, u32)> {
+        if !matches!(self.rotation, 0 | 90 | 180 | 270) {
+            anyhow::bail!("the rotation must be 90, 180 or 270 degrees");
+        }
+        let (width, height) = match self.crop {
+            Some(crop) => {
+                let (_, _, width, height) = crop.resolve(size)?;
+                (width, height)
+            }
+            None => size,
+        };
+        Ok(if self.rotation % 180 == 90 {
+            (height, width)
+        } else {
+            (width, height)
+        })
+    }
+
+    /// Applies the edits to an image.
+    pub fn apply(&self, image: RgbaImage) -> anyhow::Result<RgbaImage> {
+        let mut image = match self.crop {
+            Some(crop) => {
+                let (x, y, width, height) = crop.resolve(image.dimensions())?;
+                crop_imm(&image, x, y, width, height).to_image()
+            }
+            None => image,
+        };
+        image = match self.rotation {
+            90 => rotate90(&image),
+            180 => rotate180(&image),
+            270 => rotate270(&image),
+            _ => image,
+        };
+        if self.flip_horizontal {
+            image = flip_horizontal(&image);
+        }
+        if self.flip_vertical {
+            image = flip_vertical(&image);
+        }
+        Ok(image)
+    }
+}
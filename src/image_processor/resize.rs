@@ -0,0 +1,105 @@
+use clap::ValueEnum;
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+
+/// How the source image is fitted onto the maps.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fit {
+    /// Scale the image to fit inside the maps, keeping its aspect ratio, and pad the rest
+    Contain,
+    /// Scale the image to cover the maps, keeping its aspect ratio, and crop the rest
+    Cover,
+    /// Scale the image to exactly the size of the maps, ignoring its aspect ratio
+    Stretch,
+    /// Keep the image at its original size, padding or cropping it to the maps
+    None,
+}
+
+impl Fit {
+    /// Returns the size to scale an image of the given size to, for maps of the given size in pixels.
+    pub fn scaled_size(
+        self,
+        (width, height): (u32, u32),
+        (map_width, map_height): (u32, u32),
+    ) -> (u32, u32) {
+        let scale_x = map_width as f64 / width as f64;
+        let scale_y = map_height as f64 / height as f64;
+        let scale = match self {
+            Fit::Contain => scale_x.min(scale_y),
+            Fit::Cover => scale_x.max(scale_y),
+            Fit::Stretch => return (map_width, map_height),
+            Fit::None => return (width, height),
+        };
+        let scaled = |size: u32| ((size as f64 * scale).round() as u32).max(1);
+        (scaled(width), scaled(height))
+    }
+}
+
+/// The resampling filter used when scaling images, see `image::imageops::FilterType`.
+#[derive(Copy, Clone, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResizeFilter {
+    /// Nearest neighbour, which keeps pixel art sharp
+    Nearest,
+    /// Linear
+    Triangle,
+    /// Cubic
+    CatmullRom,
+    Gaussian,
+    /// Lanczos with a window of 3, the sharpest but slowest
+    Lanczos3,
+}
+
+impl From<ResizeFilter> for FilterType {
+    fn from(filter: ResizeFilter) -> Self {
+        match filter {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Gaussian => FilterType::Gaussian,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Where the image is placed on the maps when it does not fill them exactly,
+/// which is also the part of the image that is kept when it is cropped.
+#[derive(Copy, Clone, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Returns the position of the top left corner of an image of the given size on maps of the given size.
+    /// Negative if the image is larger than the maps, i.e. it is cropped.
+    pub fn position(
+        self,
+        (width, height): (u32, u32),
+        (map_width, map_height): (u32, u32),
+    ) -> (i64, i64) {
+        let space_x = map_width as i64 - width as i64;
+        let space_y = map_height as i64 - height as i64;
+        let (x, y) = match self {
+            Anchor::TopLeft => (0, 0),
+            Anchor::Top => (1, 0),
+            Anchor::TopRight => (2, 0),
+            Anchor::Left => (0, 1),
+            Anchor::Center => (1, 1),
+            Anchor::Right => (2, 1),
+            Anchor::BottomLeft => (0, 2),
+            Anchor::Bottom => (1, 2),
+            Anchor::BottomRight => (2, 2),
+        };
+        (space_x * x / 2, space_y * y / 2)
+    }
+}
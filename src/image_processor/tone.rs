@@ -0,0 +1,123 @@
+use image::RgbaImage;
+
+/// The fraction of the darkest and of the brightest values that auto-levels clips,
+/// so that a few stray pixels do not stop the histogram from being stretched.
+const AUTO_LEVELS_CLIP: f64 = 0.005;
+
+/// Tone adjustments applied to every frame, to make up for the muted colors of the map palette.
+/// In order: levels, brightness, contrast, saturation, then gamma.
+pub struct ToneAdjustments {
+    /// Added to every channel, from -1 to 1.
+    pub brightness: f32,
+
+    /// Multiplies the distance of every channel from middle grey, 1 is unchanged.
+    pub contrast: f32,
+
+    /// Multiplies the distance of every channel from the luma of the pixel, 1 is unchanged and 0 is greyscale.
+    pub saturation: f32,
+
+    /// Gamma correction, with values above 1 brightening the midtones, 1 is unchanged.
+    pub gamma: f32,
+
+    /// Whether the levels are stretched to the full range, as measured over the whole sequence.
+    pub auto_levels: bool,
+
+    /// The darkest and brightest values, from 0 to 1, that are stretched to black and white.
+    levels: (f32, f32),
+}
+
+/// The number of pixels with each channel value, across every channel of every opaque pixel.
+pub type Histogram = [u64; 256];
+
+impl ToneAdjustments {
+    pub fn new(
+        brightness: f32,
+        contrast: f32,
+        saturation: f32,
+        gamma: f32,
+        auto_levels: bool,
+    ) -> Self {
+        ToneAdjustments {
+            brightness,
+            contrast,
+            saturation,
+            gamma,
+            auto_levels,
+            levels: (0.0, 1.0),
+        }
+    }
+
+    /// Whether applying the adjustments changes anything.
+    fn is_identity(&self) -> bool {
+        self.brightness == 0.0
+            && self.contrast == 1.0
+            && self.saturation == 1.0
+            && self.gamma == 1.0
+            && self.levels == (0.0, 1.0)
+    }
+
+    /// Returns the histogram of an image, counting only pixels with an alpha value of at least `alpha_threshold`.
+    pub fn histogram(image: &RgbaImage, alpha_threshold: u8) -> Histogram {
+        let mut histogram = [0; 256];
+        for pixel in image.pixels().filter(|pixel| pixel[3] >= alpha_threshold) {
+            for &channel in &pixel.0[..3] {
+                histogram[channel as usize] += 1;
+            }
+        }
+        histogram
+    }
+
+    /// Sets the levels from the histogram of the whole sequence, clipping a small fraction at each end.
+    pub fn set_levels(&mut self, histogram: &Histogram) {
+        let total = histogram.iter().sum::<u64>();
+        let clip = (total as f64 * AUTO_LEVELS_CLIP) as u64;
+
+        // The first value at which the cumulative count goes past the clipped amount
+        let find = |values: &[usize]| {
+            let mut count = 0;
+            values
+                .iter()
+                .copied()
+                .find(|&value| {
+                    count += histogram[value];
+                    count > clip
+                })
+                .unwrap_or(0)
+        };
+        let mut values = (0..256).collect::<Vec<usize>>();
+        let low = find(&values);
+        values.reverse();
+        let high = find(&values);
+
+        // A flat image has nothing to stretch
+        if low < high {
+            self.levels = (low as f32 / 255.0, high as f32 / 255.0);
+        }
+    }
+
+    /// Applies the adjustments to every pixel of an image, keeping the alpha channel.
+    pub fn apply(&self, mut image: RgbaImage) -> RgbaImage {
+        if self.is_identity() {
+            return image;
+        }
+
+        let (low, high) = self.levels;
+        for pixel in image.pixels_mut() {
+            let mut rgb = [pixel[0], pixel[1], pixel[2]].map(|channel| {
+                let value = (channel as f32 / 255.0 - low) / (high - low);
+                (value + self.brightness - 0.5) * self.contrast + 0.5
+            });
+
+            let luma = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+            for value in rgb.iter_mut() {
+                *value = luma + (*value - luma) * self.saturation;
+            }
+
+            for (channel, value) in pixel.0.iter_mut().zip(rgb) {
+                let value = value.clamp(0.0, 1.0).powf(1.0 / self.gamma);
+                *channel = (value * 255.0).round() as u8;
+            }
+        }
+        image
+    }
+}
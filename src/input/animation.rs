@@ -0,0 +1,73 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::io::Reader;
+use image::{AnimationDecoder, ImageFormat};
+
+use crate::input::{Frame, FrameSource};
+
+/// GIF frames with a shorter delay than this, including 0, use the default delay instead.
+const MIN_GIF_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_GIF_DELAY: Duration = Duration::from_millis(100);
+
+/// Decodes every frame of an animated GIF, PNG (APNG) or WebP file.
+/// The decoders composite each frame onto the previous ones according to its disposal and blending,
+/// so every frame is a complete image.
+/// Returns `None` if the file is not an animation, including PNG and WebP files with a single image.
+pub fn decode(path: &Path) -> anyhow::Result<Option<Vec<Frame>>> {
+    let open = || -> anyhow::Result<BufReader<File>> {
+        Ok(BufReader::new(File::open(path).with_context(|| {
+            format!("failed to open {}", path.display())
+        })?))
+    };
+    let Some(format) = Reader::new(open()?).with_guessed_format()?.format() else {
+        return Ok(None);
+    };
+
+    let context = || format!("failed to decode {} as {format:?}", path.display());
+    let frames = match format {
+        ImageFormat::Gif => GifDecoder::new(open()?)
+            .with_context(context)?
+            .into_frames(),
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(open()?).with_context(context)?;
+            if !decoder.is_apng() {
+                return Ok(None);
+            }
+            decoder.apng().into_frames()
+        }
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(open()?).with_context(context)?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.into_frames()
+        }
+        _ => return Ok(None),
+    };
+
+    // Like browsers, play GIF frames that are too short to be meant literally at 10 frames per second
+    let fix_delay = |delay: Duration| match format {
+        ImageFormat::Gif if delay < MIN_GIF_DELAY => DEFAULT_GIF_DELAY,
+        _ => delay,
+    };
+
+    let frames = frames
+        .collect_frames()
+        .with_context(|| format!("failed to decode the frames of {}", path.display()))?
+        .into_iter()
+        .enumerate()
+        .map(|(index, frame)| Frame {
+            delay: Some(fix_delay(Duration::from(frame.delay()))),
+            source: FrameSource::Decoded(Arc::new(frame.into_buffer()), index),
+        })
+        .collect();
+    Ok(Some(frames))
+}
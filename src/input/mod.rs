@@ -0,0 +1,195 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use image::io::Reader;
+use image::{ImageFormat, RgbaImage};
+
+use crate::input::sequence::{natural_cmp, read_frame_list, Pattern};
+
+mod animation;
+mod selection;
+mod sequence;
+
+/// Options that control which files make up the sequence of frames.
+pub struct InputOptions {
+    /// The first number of a file name pattern.
+    pub start_number: Option<usize>,
+
+    /// The last number of a file name pattern.
+    pub end_number: Option<usize>,
+
+    /// The index of the first frame to keep, counting from 0.
+    pub first_frame: Option<usize>,
+
+    /// The index of the last frame to keep, inclusive.
+    pub last_frame: Option<usize>,
+
+    /// Only every `frame_step`th frame is kept.
+    pub frame_step: usize,
+
+    /// The frame rate of the input, which overrides the delays of an animated file.
+    pub source_fps: Option<f64>,
+
+    /// The frame rate to resample the input to, by dropping or repeating frames.
+    pub target_fps: Option<f64>,
+}
+
+/// One frame of the animation to convert.
+#[derive(Clone)]
+pub struct Frame {
+    pub source: FrameSource,
+
+    /// How long the frame is shown for, if the input specifies it.
+    pub delay: Option<Duration>,
+}
+
+/// Where the image of a frame comes from.
+#[derive(Clone)]
+pub enum FrameSource {
+    /// An image file, which is read when the frame is processed.
+    File(PathBuf),
+
+    /// A frame already decoded from an animated file, with its index in the animation.
+    /// Shared, as resampling the frame rate can repeat frames.
+    Decoded(Arc<RgbaImage>, usize),
+}
+
+impl Frame {
+    /// Returns the image of the frame, converted to 8-bit RGBA whatever its color type.
+    /// Files are decoded according to their contents, not their extension.
+    pub fn open(&self) -> anyhow::Result<RgbaImage> {
+        match &self.source {
+            FrameSource::File(path) => {
+                let reader = Reader::open(path)
+                    .and_then(Reader::with_guessed_format)
+                    .with_context(|| format!("failed to open {}", path.display()))?;
+                let format = reader.format();
+                let image = reader.decode().with_context(|| match format {
+                    Some(format) => format!("failed to decode {} as {format:?}", path.display()),
+                    None => format!("{} is not in a supported image format", path.display()),
+                })?;
+                Ok(image.to_rgba8())
+            }
+            FrameSource::Decoded(image, _) => Ok(RgbaImage::clone(image)),
+        }
+    }
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.source {
+            FrameSource::File(path) => write!(f, "{}", path.display()),
+            FrameSource::Decoded(_, index) => write!(f, "frame {} of the animation", index + 1),
+        }
+    }
+}
+
+/// Reads the frames to convert from the input path, which is one of:
+/// - a folder of images, in natural order
+/// - a printf-style file name pattern, such as `frames/frame_%05d.png`
+/// - a frame list, a text file with the path of one frame per line
+/// - an animated GIF, PNG or WebP file
+/// - a single still image
+///
+/// The frames are then narrowed down to the range, stride and frame rate of the options.
+pub fn read_frames(input_path: &Path, options: &InputOptions) -> anyhow::Result<Vec<Frame>> {
+    let frames = read_all_frames(input_path, options)?;
+    let total = frames.len();
+    let frames = selection::select(frames, options)?;
+    if frames.len() != total {
+        println!("Selected {} of {} frames", frames.len(), total);
+    }
+    Ok(frames)
+}
+
+/// Reads every frame of the input path, as described in `read_frames`.
+fn read_all_frames(input_path: &Path, options: &InputOptions) -> anyhow::Result<Vec<Frame>> {
+    let paths = if let Some(pattern) = Pattern::parse(input_path) {
+        let paths = pattern.files(options.start_number, options.end_number)?;
+        if paths.is_empty() {
+            anyhow::bail!("no images found matching {}", input_path.display());
+        }
+        paths
+    } else if input_path.is_file() {
+        if let Some(frames) = animation::decode(input_path)? {
+            let duration = frames
+                .iter()
+                .filter_map(|frame| frame.delay)
+                .sum::<Duration>();
+            println!(
+                "Decoded {} frames from {}, lasting {}.{:03} seconds",
+                frames.len(),
+                input_path.display(),
+                duration.as_secs(),
+                duration.subsec_millis()
+            );
+            return Ok(frames);
+        }
+
+        // A text file, or a file that is an image by neither its contents nor its extension, is read as a frame list.
+        // Any other file is an image, and opening it reports why it cannot be decoded
+        let is_text = input_path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("txt"));
+        if is_text
+            || (ImageFormat::from_path(input_path).is_err() && detect_format(input_path).is_none())
+        {
+            let paths = read_frame_list(input_path)?;
+            if paths.is_empty() {
+                anyhow::bail!("the frame list {} is empty", input_path.display());
+            }
+            paths
+        } else {
+            vec![input_path.to_path_buf()]
+        }
+    } else {
+        read_folder(input_path)?
+    };
+
+    Ok(paths
+        .into_iter()
+        .map(|path| Frame {
+            source: FrameSource::File(path),
+            delay: None,
+        })
+        .collect())
+}
+
+/// Returns every image in a folder, sorted by name in natural order.
+/// Files that are not images are skipped with a warning.
+fn read_folder(folder: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(folder).with_context(|| "failed to read input directory")? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        match detect_format(&path) {
+            Some(format) if format.can_read() => entries.push(path),
+            Some(format) => println!(
+                "Skipping {}: {format:?} images are not supported",
+                path.display()
+            ),
+            None => println!("Skipping {}: not an image", path.display()),
+        }
+    }
+
+    if entries.is_empty() {
+        anyhow::bail!("no images found in input directory");
+    }
+
+    // Sort images by name, so that frame2.png comes before frame10.png
+    entries.sort_unstable_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+    Ok(entries)
+}
+
+/// Returns the format of an image file, from its contents if they are recognized, or else from its extension.
+fn detect_format(path: &Path) -> Option<ImageFormat> {
+    Reader::open(path)
+        .and_then(Reader::with_guessed_format)
+        .ok()?
+        .format()
+}
@@ -0,0 +1,287 @@
+use std::time::Duration;
+
+use crate::input::{Frame, InputOptions};
+
+/// Narrows the frames down to the range of the options, then keeps every `frame_step`th frame,
+/// then resamples them to the target frame rate.
+/// Every kept frame lasts as long as the frames it stands in for, so the timing of the animation is kept.
+pub fn select(mut frames: Vec<Frame>, options: &InputOptions) -> anyhow::Result<Vec<Frame>> {
+    // The source frame rate replaces any delays from the input
+    if let Some(fps) = options.source_fps {
+        let delay = frame_duration(fps, "--source-fps")?;
+        frames
+            .iter_mut()
+            .for_each(|frame| frame.delay = Some(delay));
+    }
+
+    if frames.is_empty() {
+        anyhow::bail!("the input has no frames");
+    }
+
+    let first = options.first_frame.unwrap_or(0);
+    let last = options
+        .last_frame
+        .unwrap_or(usize::MAX)
+        .min(frames.len().saturating_sub(1));
+    if first > last {
+        anyhow::bail!(
+            "the frame range {first} to {last} is empty, the input has {} frames",
+            frames.len()
+        );
+    }
+    frames.truncate(last + 1);
+    frames.drain(..first);
+
+    if options.frame_step == 0 {
+        anyhow::bail!("the frame step must be at least 1");
+    }
+    if options.frame_step > 1 {
+        frames = frames
+            .chunks(options.frame_step)
+            .map(|chunk| {
+                let mut frame = chunk[0].clone();
+                frame.delay = chunk.iter().map(|frame| frame.delay).sum();
+                frame
+            })
+            .collect();
+    }
+
+    let frames = match options.target_fps {
+        Some(fps) => resample(frames, frame_duration(fps, "--target-fps")?)?,
+        None => frames,
+    };
+    if frames.is_empty() {
+        anyhow::bail!("no frames are left after selecting them");
+    }
+    Ok(frames)
+}
+
+/// Returns how long a frame lasts at the given frame rate.
+fn frame_duration(fps: f64, flag: &str) -> anyhow::Result<Duration> {
+    if !(fps > 0.0 && fps.is_finite()) {
+        anyhow::bail!("{flag} must be a positive number");
+    }
+    Ok(Duration::from_secs_f64(1.0 / fps))
+}
+
+/// Resamples the frames to a constant frame duration, by showing the frame that is visible
+/// at the start of every new frame. Frames shorter than the new duration may be dropped,
+/// and frames longer than it are repeated.
+fn resample(frames: Vec<Frame>, duration: Duration) -> anyhow::Result<Vec<Frame>> {
+    let delays = frames
+        .iter()
+        .map(|frame| frame.delay)
+        .collect::<Option<Vec<Duration>>>()
+        .ok_or_else(|| {
+            anyhow::anyhow!("--source-fps is required to resample frames that have no timing")
+        })?;
+
+    // The time at which every source frame ends
+    let ends = delays
+        .iter()
+        .scan(Duration::ZERO, |end, delay| {
+            *end += *delay;
+            Some(*end)
+        })
+        .collect::<Vec<Duration>>();
+    let total = ends.last().copied().unwrap_or_default();
+    if total.is_zero() {
+        anyhow::bail!("the frames have no duration to resample, --source-fps can give them one");
+    }
+
+    let mut resampled = Vec::new();
+    let mut source = 0;
+    while resampled.len() as f64 * duration.as_secs_f64() < total.as_secs_f64() {
+        let time = duration.mul_f64(resampled.len() as f64);
+        while ends[source] <= time {
+            source += 1;
+        }
+        let mut frame = frames[source].clone();
+        frame.delay = Some(duration);
+        resampled.push(frame);
+    }
+    Ok(resampled)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::input::FrameSource;
+
+    /// Frames named after their index, lasting the given number of milliseconds each.
+    fn frames(delays: &[u64]) -> Vec<Frame> {
+        delays
+            .iter()
+            .enumerate()
+            .map(|(index, &delay)| Frame {
+                source: FrameSource::File(PathBuf::from(index.to_string())),
+                delay: Some(Duration::from_millis(delay)),
+            })
+            .collect()
+    }
+
+    fn options() -> InputOptions {
+        InputOptions {
+            start_number: None,
+            end_number: None,
+            first_frame: None,
+            last_frame: None,
+            frame_step: 1,
+            source_fps: None,
+            target_fps: None,
+        }
+    }
+
+    /// The index of the source frame of every selected frame.
+    fn indices(frames: &[Frame]) -> Vec<usize> {
+        frames
+            .iter()
+            .map(|frame| match &frame.source {
+                FrameSource::File(path) => path.to_str().unwrap().parse().unwrap(),
+                FrameSource::Decoded(_, index) => *index,
+            })
+            .collect()
+    }
+
+    fn delays(frames: &[Frame]) -> Vec<u128> {
+        frames
+            .iter()
+            .map(|frame| frame.delay.unwrap().as_millis())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_every_frame_by_default() {
+        let selected = select(frames(&[100; 5]), &options()).unwrap();
+        assert_eq!(indices(&selected), [0, 1, 2, 3, 4]);
+        assert_eq!(delays(&selected), [100; 5]);
+    }
+
+    #[test]
+    fn selects_a_range() {
+        let options = InputOptions {
+            first_frame: Some(2),
+            last_frame: Some(5),
+            ..options()
+        };
+        let selected = select(frames(&[100; 10]), &options).unwrap();
+        assert_eq!(indices(&selected), [2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn clamps_the_last_frame_to_the_input() {
+        let options = InputOptions {
+            first_frame: Some(7),
+            last_frame: Some(100),
+            ..options()
+        };
+        let selected = select(frames(&[100; 10]), &options).unwrap();
+        assert_eq!(indices(&selected), [7, 8, 9]);
+    }
+
+    #[test]
+    fn rejects_an_empty_range() {
+        let options = InputOptions {
+            first_frame: Some(10),
+            ..options()
+        };
+        assert!(select(frames(&[100; 10]), &options).is_err());
+    }
+
+    #[test]
+    fn rejects_an_empty_input() {
+        assert!(select(Vec::new(), &options()).is_err());
+    }
+
+    #[test]
+    fn steps_over_frames_and_keeps_their_duration() {
+        let options = InputOptions {
+            frame_step: 3,
+            ..options()
+        };
+        let selected = select(frames(&[100; 10]), &options).unwrap();
+        assert_eq!(indices(&selected), [0, 3, 6, 9]);
+        assert_eq!(delays(&selected), [300, 300, 300, 100]);
+    }
+
+    #[test]
+    fn rejects_a_zero_step() {
+        let options = InputOptions {
+            frame_step: 0,
+            ..options()
+        };
+        assert!(select(frames(&[100; 10]), &options).is_err());
+    }
+
+    #[test]
+    fn downsamples_by_dropping_frames() {
+        let options = InputOptions {
+            target_fps: Some(5.0),
+            ..options()
+        };
+        let selected = select(frames(&[100; 6]), &options).unwrap();
+        assert_eq!(indices(&selected), [0, 2, 4]);
+        assert_eq!(delays(&selected), [200; 3]);
+    }
+
+    #[test]
+    fn upsamples_by_repeating_frames() {
+        let options = InputOptions {
+            target_fps: Some(20.0),
+            ..options()
+        };
+        let selected = select(frames(&[100; 2]), &options).unwrap();
+        assert_eq!(indices(&selected), [0, 0, 1, 1]);
+        assert_eq!(delays(&selected), [50; 4]);
+    }
+
+    #[test]
+    fn resamples_frames_of_different_durations() {
+        let options = InputOptions {
+            target_fps: Some(10.0),
+            ..options()
+        };
+        let selected = select(frames(&[50, 150, 300]), &options).unwrap();
+        assert_eq!(indices(&selected), [0, 1, 2, 2, 2]);
+    }
+
+    #[test]
+    fn resamples_with_the_source_frame_rate() {
+        let options = InputOptions {
+            source_fps: Some(20.0),
+            target_fps: Some(10.0),
+            ..options()
+        };
+        let selected = select(frames(&[1000; 4]), &options).unwrap();
+        assert_eq!(indices(&selected), [0, 2]);
+        assert_eq!(delays(&selected), [100; 2]);
+    }
+
+    #[test]
+    fn rejects_resampling_frames_without_duration() {
+        let options = InputOptions {
+            target_fps: Some(10.0),
+            ..options()
+        };
+        assert!(select(frames(&[0; 3]), &options).is_err());
+
+        let mut without_delays = frames(&[100; 3]);
+        without_delays
+            .iter_mut()
+            .for_each(|frame| frame.delay = None);
+        assert!(select(without_delays, &options).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_frame_rates() {
+        for fps in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let options = InputOptions {
+                target_fps: Some(fps),
+                ..options()
+            };
+            assert!(select(frames(&[100; 3]), &options).is_err());
+        }
+    }
+}
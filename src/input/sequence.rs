@@ -0,0 +1,277 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+/// Compares two strings in natural order, where runs of digits are compared by their numeric value,
+/// so that `frame2.png` comes before `frame10.png`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (a_chunk, a_rest) = split_chunk(a);
+        let (b_chunk, b_rest) = split_chunk(b);
+        if a_chunk.is_empty() || b_chunk.is_empty() {
+            return a_chunk.len().cmp(&b_chunk.len());
+        }
+
+        let a_digits = a_chunk.starts_with(|c: char| c.is_ascii_digit());
+        let b_digits = b_chunk.starts_with(|c: char| c.is_ascii_digit());
+        let ordering = if a_digits && b_digits {
+            // Compare numbers of any length without parsing them: fewer digits is smaller,
+            // then digit by digit. Leading zeros only break ties
+            let (a_number, b_number) = (
+                a_chunk.trim_start_matches('0'),
+                b_chunk.trim_start_matches('0'),
+            );
+            a_number
+                .len()
+                .cmp(&b_number.len())
+                .then_with(|| a_number.cmp(b_number))
+                .then_with(|| a_chunk.len().cmp(&b_chunk.len()))
+        } else {
+            a_chunk.cmp(b_chunk)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        (a, b) = (a_rest, b_rest);
+    }
+}
+
+/// Splits off the leading run of digits, or of anything else, from a string.
+fn split_chunk(s: &str) -> (&str, &str) {
+    let digits = s.starts_with(|c: char| c.is_ascii_digit());
+    let end = s
+        .find(|c: char| c.is_ascii_digit() != digits)
+        .unwrap_or(s.len());
+    s.split_at(end)
+}
+
+/// A printf-style file name pattern with a single number in it, such as `frame_%05d.png`.
+pub struct Pattern {
+    prefix: String,
+    /// The number is padded with zeros to this many digits.
+    width: usize,
+    suffix: String,
+}
+
+impl Pattern {
+    /// Parses a pattern, returning `None` if the path has no `%d` (or `%0Nd`) in it.
+    /// A literal `%` is written as `%%`.
+    pub fn parse(path: &Path) -> Option<Self> {
+        let path = path.to_str()?;
+        let mut prefix = String::new();
+        let mut chars = path.char_indices().peekable();
+        while let Some((_, c)) = chars.next() {
+            if c != '%' {
+                prefix.push(c);
+                continue;
+            }
+            if let Some((_, '%')) = chars.peek() {
+                chars.next();
+                prefix.push('%');
+                continue;
+            }
+
+            // The width, if any, is written as zero followed by the number of digits
+            let mut width = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !c.is_ascii_digit() {
+                    break;
+                }
+                width.push(c);
+                chars.next();
+            }
+            return match chars.next() {
+                Some((i, 'd')) => Some(Pattern {
+                    prefix,
+                    width: width.parse().unwrap_or(0),
+                    suffix: path[i + 1..].replace("%%", "%"),
+                }),
+                _ => None,
+            };
+        }
+        None
+    }
+
+    /// Returns the path of the file with the given number.
+    fn path(&self, number: usize) -> PathBuf {
+        PathBuf::from(format!(
+            "{}{:0width$}{}",
+            self.prefix,
+            number,
+            self.suffix,
+            width = self.width
+        ))
+    }
+
+    /// Returns every file of the pattern from `start` to `end`, inclusive.
+    /// Without a start, the sequence starts at 0 or 1, whichever exists.
+    /// Without an end, the sequence ends before the first missing file, otherwise a missing file is an error.
+    pub fn files(&self, start: Option<usize>, end: Option<usize>) -> anyhow::Result<Vec<PathBuf>> {
+        let start = match start {
+            Some(start) => start,
+            None if !self.path(0).exists() && self.path(1).exists() => 1,
+            None => 0,
+        };
+
+        let mut files = Vec::new();
+        for number in start.. {
+            if end.is_some_and(|end| number > end) {
+                break;
+            }
+            let path = self.path(number);
+            if !path.is_file() {
+                if end.is_some() {
+                    anyhow::bail!("{} is missing from the sequence", path.display());
+                }
+                break;
+            }
+            files.push(path);
+        }
+        Ok(files)
+    }
+}
+
+/// Reads a frame list file, which has the path of one frame per line, in order.
+/// Relative paths are relative to the folder of the list. Blank lines and lines starting with `#` are ignored.
+pub fn read_frame_list(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read the frame list {}", path.display()))?;
+    let folder = path.parent().unwrap_or(Path::new(""));
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| folder.join(line))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The path of the given number in the pattern, as a string.
+    fn pattern_path(pattern: &str, number: usize) -> String {
+        Pattern::parse(Path::new(pattern))
+            .unwrap()
+            .path(number)
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    /// A new empty folder holding the given files.
+    fn folder(name: &str, files: &[&str]) -> PathBuf {
+        let folder =
+            std::env::temp_dir().join(format!("mapmaker-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        for file in files {
+            fs::write(folder.join(file), "").unwrap();
+        }
+        folder
+    }
+
+    #[test]
+    fn split_chunk_splits_digits_from_the_rest() {
+        assert_eq!(split_chunk("123abc"), ("123", "abc"));
+        assert_eq!(split_chunk("abc123"), ("abc", "123"));
+        assert_eq!(split_chunk("007"), ("007", ""));
+        assert_eq!(split_chunk(""), ("", ""));
+    }
+
+    #[test]
+    fn numbers_are_compared_by_value() {
+        assert_eq!(natural_cmp("frame2.png", "frame10.png"), Ordering::Less);
+        assert_eq!(natural_cmp("frame10.png", "frame9.png"), Ordering::Greater);
+        assert_eq!(natural_cmp("frame007.png", "frame10.png"), Ordering::Less);
+        assert_eq!(natural_cmp("1_10", "1_9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn numbers_longer_than_64_bits_are_compared() {
+        assert_eq!(
+            natural_cmp("a99999999999999999999999", "a100000000000000000000000"),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn leading_zeros_only_break_ties() {
+        assert_eq!(natural_cmp("frame1", "frame01"), Ordering::Less);
+        assert_eq!(natural_cmp("frame01", "frame2"), Ordering::Less);
+        assert_eq!(natural_cmp("frame01", "frame01"), Ordering::Equal);
+    }
+
+    #[test]
+    fn text_is_compared_as_is() {
+        assert_eq!(natural_cmp("a", "b"), Ordering::Less);
+        assert_eq!(natural_cmp("frame", "frame1"), Ordering::Less);
+        assert_eq!(natural_cmp("1", "a"), Ordering::Less);
+        assert_eq!(natural_cmp("", ""), Ordering::Equal);
+    }
+
+    #[test]
+    fn patterns_fill_in_the_number() {
+        assert_eq!(pattern_path("frame_%d.png", 12), "frame_12.png");
+        assert_eq!(pattern_path("%d", 3), "3");
+    }
+
+    #[test]
+    fn patterns_pad_the_number_with_zeros() {
+        assert_eq!(pattern_path("frame_%05d.png", 7), "frame_00007.png");
+        assert_eq!(pattern_path("frame_%03d.png", 1234), "frame_1234.png");
+    }
+
+    #[test]
+    fn double_percent_signs_are_literal() {
+        assert_eq!(pattern_path("100%%_%d.png", 3), "100%_3.png");
+        assert_eq!(pattern_path("%d_%%.png", 3), "3_%.png");
+    }
+
+    #[test]
+    fn paths_without_a_number_are_not_patterns() {
+        assert!(Pattern::parse(Path::new("frames")).is_none());
+        assert!(Pattern::parse(Path::new("frame.png")).is_none());
+        assert!(Pattern::parse(Path::new("100%%.png")).is_none());
+    }
+
+    #[test]
+    fn a_percent_sign_not_followed_by_d_is_not_a_pattern() {
+        assert!(Pattern::parse(Path::new("50%.png")).is_none());
+        assert!(Pattern::parse(Path::new("frame_%s.png")).is_none());
+        assert!(Pattern::parse(Path::new("frame_%")).is_none());
+    }
+
+    #[test]
+    fn sequences_start_at_0_or_1_and_end_before_a_missing_file() {
+        let from_1 = folder("from-1", &["f1.png", "f2.png", "f3.png", "f5.png"]);
+        let files = Pattern::parse(&from_1.join("f%d.png"))
+            .unwrap()
+            .files(None, None)
+            .unwrap();
+        assert_eq!(files, [1, 2, 3].map(|n| from_1.join(format!("f{n}.png"))));
+
+        let from_0 = folder("from-0", &["f000.png", "f001.png"]);
+        let files = Pattern::parse(&from_0.join("f%03d.png"))
+            .unwrap()
+            .files(None, None)
+            .unwrap();
+        assert_eq!(files, ["f000.png", "f001.png"].map(|f| from_0.join(f)));
+
+        fs::remove_dir_all(from_1).unwrap();
+        fs::remove_dir_all(from_0).unwrap();
+    }
+
+    #[test]
+    fn a_missing_file_before_the_end_is_an_error() {
+        let folder = folder("missing", &["f1.png", "f2.png", "f4.png"]);
+        let pattern = Pattern::parse(&folder.join("f%d.png")).unwrap();
+        assert_eq!(pattern.files(Some(2), Some(2)).unwrap().len(), 1);
+        assert!(pattern.files(Some(1), Some(4)).is_err());
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+}
@@ -1,15 +1,15 @@
 use anyhow::Context;
 use indicatif::ParallelProgressIterator;
-use std::fs;
-use std::path::PathBuf;
 
 use rayon::prelude::*;
 
 use crate::image_processor::Processor;
+use crate::output_generator::timing::Timing;
 use crate::output_generator::Generator;
 
 mod cli;
 mod image_processor;
+mod input;
 mod output_generator;
 
 fn main() -> anyhow::Result<()> {
@@ -19,54 +19,44 @@ fn main() -> anyhow::Result<()> {
         args.starting_index,
         args.top_left,
         args.direction,
+        args.top,
+        args.version,
+        &args.display,
     )?;
 
-    let mut entries = fs::read_dir(&args.input_path)
-        .with_context(|| "failed to read input directory")?
-        .filter_map(|entry| {
-            if let Ok(entry) = entry {
-                if let Some(ext) = entry.path().extension() {
-                    if let Some("jpg") | Some("jpeg") | Some("png") = ext.to_str() {
-                        return Some(entry.path());
-                    }
-                }
-            }
-            None
-        })
-        .collect::<Vec<PathBuf>>();
-
-    if entries.is_empty() {
-        anyhow::bail!("no images found in input directory");
-    }
-
-    // Sort images by name
-    entries.sort_unstable();
+    let frames = input::read_frames(&args.input_path, &args.input)?;
+    let delays = frames.iter().map(|frame| frame.delay).collect::<Vec<_>>();
+    let timing = Timing::new(args.fps, &delays)?;
 
     // Get the first image to initialize the processor with the dimensions
-    let processor = Processor::new(&entries[0])?;
+    let first_frame = frames.first().context("the input has no frames")?;
+    let mut processor = Processor::new(first_frame, args.version, args.processing)?;
+    processor.measure_levels(&frames)?;
+
+    // Nothing may be written to a world before making sure that no maps would be overwritten
+    let (map_columns, map_rows) = (processor.map_columns as usize, processor.map_rows as usize);
+    if args.world.is_some() {
+        generator.check_existing_maps(frames.len(), map_columns, map_rows)?;
+    }
 
     // Time the conversion process
     println!("Starting conversion process...");
     let start = std::time::Instant::now();
 
-    let generator = generator.init_files(
-        entries.len(),
-        processor.map_columns as usize,
-        processor.map_rows as usize,
-    )?;
+    let generator = generator.init_files(frames.len(), map_columns, map_rows)?;
 
-    entries
+    frames
         .par_iter()
         .enumerate()
-        .progress_count(entries.len() as u64)
-        .for_each(|(frame, entry)| {
-            let image = processor.process_file(entry).unwrap();
+        .progress_count(frames.len() as u64)
+        .try_for_each(|(index, frame)| {
+            let image = processor.process_frame(frame)?;
             let maps = processor.convert_colors(image);
 
-            maps.par_iter().enumerate().for_each(|(i, map)| {
-                generator.generate_dat(map, i, frame).unwrap();
-            });
-        });
+            maps.par_iter()
+                .enumerate()
+                .try_for_each(|(i, map)| generator.generate_dat(map, i, index))
+        })?;
 
     let duration = start.elapsed();
     println!(
@@ -76,7 +66,7 @@ fn main() -> anyhow::Result<()> {
     );
 
     generator.generate_idcounts()?;
-    generator.generate_datapack()?;
+    generator.generate_datapack(&timing, &args.playback)?;
 
     Ok(())
 }
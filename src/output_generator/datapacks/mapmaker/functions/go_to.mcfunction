@@ -0,0 +1,9 @@
+# Keep the frame within the animation
+execute if score {holder} frame matches ..-1 run scoreboard players set {holder} frame 0
+execute if score {holder} frame >= {holder} frames run scoreboard players operation {holder} frame = {holder} frames
+execute if score {holder} frame >= {holder} frames run scoreboard players remove {holder} frame 1
+# A finished animation plays on from the new frame
+execute if score {holder} finished matches 1 run scoreboard players set {holder} loops 0
+scoreboard players set {holder} finished 0
+scoreboard players set {holder} clock 0
+function {namespace}:show
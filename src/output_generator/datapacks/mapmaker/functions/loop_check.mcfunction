@@ -1,4 +1,6 @@
+# Let players control playback, if they are allowed to
+function {namespace}:triggers
 # Give Minecraft time to render everything the first time, about 10 ticks per iteration
-execute if score Global ready matches 1 unless score Global paused matches 1 run function mapmaker:loop
-execute unless score Global ready matches 1 unless score Global paused matches 1 run scoreboard players add Global ticker 1
-execute unless score Global ready matches 1 unless score Global paused matches 1 if score Global ticker matches 10 run function mapmaker:render
\ No newline at end of file
+execute if score {holder} ready matches 1 unless score {holder} paused matches 1 unless score {holder} finished matches 1 run function {namespace}:playback
+execute unless score {holder} ready matches 1 unless score {holder} paused matches 1 run scoreboard players add {holder} ticker 1
+execute unless score {holder} ready matches 1 unless score {holder} paused matches 1 if score {holder} ticker matches 10 run function {namespace}:render
\ No newline at end of file
@@ -0,0 +1,2 @@
+# Pause the animation on the current frame
+scoreboard players set {holder} paused 1
@@ -0,0 +1,3 @@
+# Play the animation, from the start if it has finished playing
+execute if score {holder} ready matches 1 if score {holder} finished matches 1 run function {namespace}:restart
+scoreboard players set {holder} paused 0
@@ -1,4 +1,7 @@
-scoreboard players set Global ticker 0
-function mapmaker:loop
-scoreboard players add Global rendering 1
-execute if score Global rendering > Global frames run scoreboard players set Global ready 1
\ No newline at end of file
+scoreboard players set {holder} ticker 0
+scoreboard players add {holder} frame 1
+scoreboard players operation {holder} frame %= {holder} frames
+function {namespace}:show
+scoreboard players add {holder} rendering 1
+execute if score {holder} rendering > {holder} frames run function {namespace}:restart
+execute if score {holder} rendering > {holder} frames run scoreboard players set {holder} ready 1
\ No newline at end of file
@@ -0,0 +1,3 @@
+# Show the frame in the score {holder} seek, counting from 0, without changing whether the animation plays
+execute if score {holder} ready matches 1 run scoreboard players operation {holder} frame = {holder} seek
+execute if score {holder} ready matches 1 run function {namespace}:go_to
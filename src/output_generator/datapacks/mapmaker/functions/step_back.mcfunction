@@ -0,0 +1,5 @@
+# Pause the animation and show the previous frame, or the last frame before the first one
+scoreboard players set {holder} paused 1
+execute if score {holder} ready matches 1 run scoreboard players remove {holder} frame 1
+execute if score {holder} ready matches 1 if score {holder} frame matches ..-1 run scoreboard players operation {holder} frame += {holder} frames
+execute if score {holder} ready matches 1 run function {namespace}:go_to
@@ -0,0 +1,5 @@
+# Pause the animation and show the next frame, or the first frame after the last one
+scoreboard players set {holder} paused 1
+execute if score {holder} ready matches 1 run scoreboard players add {holder} frame 1
+execute if score {holder} ready matches 1 if score {holder} frame >= {holder} frames run scoreboard players set {holder} frame 0
+execute if score {holder} ready matches 1 run function {namespace}:go_to
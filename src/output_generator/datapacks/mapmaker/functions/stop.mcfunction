@@ -0,0 +1,3 @@
+# Pause the animation and go back to the first frame
+scoreboard players set {holder} paused 1
+execute if score {holder} ready matches 1 run function {namespace}:restart
@@ -0,0 +1,5 @@
+# Pause the animation if it is playing, or else play it
+scoreboard players set {holder} playing 0
+execute unless score {holder} paused matches 1 unless score {holder} finished matches 1 run scoreboard players set {holder} playing 1
+execute if score {holder} playing matches 1 run function {namespace}:pause
+execute if score {holder} playing matches 0 run function {namespace}:play
@@ -2,7 +2,26 @@
 pub(super) const LOOP_CHECK_MCFUNCTION: &str =
     include_str!("mapmaker/functions/loop_check.mcfunction");
 pub(super) const RENDER_MCFUNCTION: &str = include_str!("mapmaker/functions/render.mcfunction");
-pub(super) const PACK_MCMETA: &str = include_str!("pack.mcmeta");
+/// The functions that control playback, which map builders can run from buttons and command blocks.
+pub(super) const CONTROL_MCFUNCTIONS: [(&str, &str); 8] = [
+    ("play", include_str!("mapmaker/functions/play.mcfunction")),
+    ("pause", include_str!("mapmaker/functions/pause.mcfunction")),
+    (
+        "toggle",
+        include_str!("mapmaker/functions/toggle.mcfunction"),
+    ),
+    ("stop", include_str!("mapmaker/functions/stop.mcfunction")),
+    (
+        "step_forward",
+        include_str!("mapmaker/functions/step_forward.mcfunction"),
+    ),
+    (
+        "step_back",
+        include_str!("mapmaker/functions/step_back.mcfunction"),
+    ),
+    ("seek", include_str!("mapmaker/functions/seek.mcfunction")),
+    ("go_to", include_str!("mapmaker/functions/go_to.mcfunction")),
+];
 pub(super) const LOAD_JSON: &str = include_str!("minecraft/tags/functions/load.json");
 pub(super) const TICK_JSON: &str = include_str!("minecraft/tags/functions/tick.json");
 pub(super) const HEADER: &str = include_str!("mapmaker/functions/templates/header.in");
@@ -0,0 +1,53 @@
+/// The names that a display's datapack uses for its functions, scores, tags and storage,
+/// so that several displays can live in the same world and be controlled independently.
+pub struct Display {
+    /// `None` for the default display, which keeps the namespace and tags from before displays could be named.
+    name: Option<String>,
+    /// The fake player that holds the scores, named after the namespace.
+    holder: String,
+}
+
+impl Display {
+    /// Checks that a display name can be used as a datapack namespace, a scoreboard objective and an entity tag.
+    pub fn new(name: Option<String>) -> anyhow::Result<Self> {
+        if let Some(name) = &name {
+            // The item frames are tagged with the name, and with the name and their map ID
+            // separated by a dot, which a name cannot contain, so no tag is shared between displays
+            if !name.starts_with(|c: char| c.is_ascii_lowercase())
+                || name.len() > 32
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            {
+                anyhow::bail!(
+                    "the display name must be 1 to 32 lowercase letters, digits or underscores, starting with a letter"
+                );
+            }
+            if name == "minecraft" || name == "mapmaker" {
+                anyhow::bail!("the display name cannot be {name}");
+            }
+        }
+        let holder = format!("#{}", name.as_deref().unwrap_or("mapmaker"));
+        Ok(Display { name, holder })
+    }
+
+    /// The namespace of the functions and storage, which also names the datapack folder,
+    /// the tag of the item frames and the trigger objectives.
+    pub fn namespace(&self) -> &str {
+        self.name.as_deref().unwrap_or("mapmaker")
+    }
+
+    /// The fake player that holds the scores of the display.
+    /// It starts with `#`, which no player name can, so no player shares its scores,
+    /// and which also keeps it out of the sidebar.
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    /// Fills in the names of the display in a datapack file that needs nothing else filled in.
+    pub fn fill(&self, contents: &str) -> String {
+        contents
+            .replace("{namespace}", self.namespace())
+            .replace("{holder}", self.holder())
+    }
+}
@@ -0,0 +1,105 @@
+use std::ops::Range;
+
+/// Allocates Minecraft map IDs to every map of every frame.
+/// All generated files (the `.dat` files, `idcounts.dat` and the datapack functions)
+/// get their IDs from here so that they always agree.
+///
+/// IDs are contiguous, starting at `starting_index` and ordered by frame, then by map within the frame.
+#[derive(Copy, Clone)]
+pub struct MapIds {
+    starting_index: usize,
+    maps_per_frame: usize,
+    frames: usize,
+}
+
+impl MapIds {
+    pub fn new(starting_index: usize, maps_per_frame: usize, frames: usize) -> Self {
+        MapIds {
+            starting_index,
+            maps_per_frame,
+            frames,
+        }
+    }
+
+    /// Returns the ID of the map at `index` within the given `frame`.
+    pub fn id(&self, frame: usize, index: usize) -> usize {
+        debug_assert!(frame < self.frames && index < self.maps_per_frame);
+        self.starting_index + frame * self.maps_per_frame + index
+    }
+
+    /// Returns the ID of the first map, i.e. the top left map of the first frame.
+    pub fn first(&self) -> usize {
+        self.starting_index
+    }
+
+    /// Returns the ID of the last map, i.e. the bottom right map of the last frame.
+    pub fn last(&self) -> usize {
+        self.first() + self.total() - 1
+    }
+
+    /// Returns every allocated ID, in order.
+    pub fn all(&self) -> Range<usize> {
+        self.first()..self.first() + self.total()
+    }
+
+    pub fn maps_per_frame(&self) -> usize {
+        self.maps_per_frame
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Returns the total number of maps across all frames.
+    pub fn total(&self) -> usize {
+        self.maps_per_frame * self.frames
+    }
+
+    /// Returns true if no maps have been allocated.
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_ordered_by_frame_then_map() {
+        let ids = MapIds::new(10, 4, 3);
+        assert_eq!(ids.id(0, 0), 10);
+        assert_eq!(ids.id(0, 3), 13);
+        assert_eq!(ids.id(1, 0), 14);
+        assert_eq!(ids.id(2, 3), 21);
+    }
+
+    #[test]
+    fn first_last_and_all_agree_with_id() {
+        let ids = MapIds::new(10, 4, 3);
+        assert_eq!(ids.first(), ids.id(0, 0));
+        assert_eq!(ids.last(), ids.id(2, 3));
+        assert_eq!(ids.total(), 12);
+        assert_eq!(ids.all(), 10..22);
+        assert_eq!(ids.all().count(), ids.total());
+
+        let every_id = (0..ids.frames())
+            .flat_map(|frame| (0..ids.maps_per_frame()).map(move |index| ids.id(frame, index)))
+            .collect::<Vec<_>>();
+        assert_eq!(every_id, ids.all().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn a_single_map_is_both_first_and_last() {
+        let ids = MapIds::new(7, 1, 1);
+        assert_eq!(ids.first(), 7);
+        assert_eq!(ids.last(), 7);
+        assert_eq!(ids.all(), 7..8);
+        assert!(!ids.is_empty());
+    }
+
+    #[test]
+    fn no_frames_is_empty() {
+        assert!(MapIds::new(7, 4, 0).is_empty());
+    }
+}
@@ -1,21 +1,26 @@
 use std::fs;
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use nbt::{Blob, Map, Value};
 
-use crate::cli::constants::{Direction, Location};
+use crate::cli::constants::{Direction, Location, McVersion};
 use crate::image_processor::colors::MapColor;
 use crate::output_generator::datapacks::{
-    HEADER, LOAD_JSON, LOOP_CHECK_MCFUNCTION, PACK_MCMETA, RENDER_MCFUNCTION, TICK_JSON,
+    CONTROL_MCFUNCTIONS, HEADER, LOAD_JSON, LOOP_CHECK_MCFUNCTION, RENDER_MCFUNCTION, TICK_JSON,
 };
+use crate::output_generator::display::Display;
+use crate::output_generator::map_ids::MapIds;
+use crate::output_generator::playback::{EndFrame, Playback, PlaybackMode};
+use crate::output_generator::timing::Timing;
 
 mod datapacks;
-
-/// Used in the `DataVersion` field of the NBT file.
-/// Currently set to correspond to 1.16.5
-const NBT_DATAVERSION: i32 = 2586;
+pub mod display;
+mod map_ids;
+pub mod playback;
+pub mod timing;
+pub mod world;
 
 /// This struct is responsible for outputting the NBT and datapack files.
 /// The lifetime is bound to the path reference.
@@ -24,26 +29,37 @@ pub struct Generator<'a> {
     starting_index: usize,
     top_left: Location,
     direction: Direction,
+    /// The horizontal direction the top of the image points to. Only used for floor and ceiling displays.
+    top: Direction,
+    /// The Minecraft version profile that decides the format of the output files.
+    version: &'static McVersion,
+    /// The names of the datapack's functions, scores, tags and storage.
+    display: &'a Display,
 }
 
 pub struct InitializedGenerator<'a> {
     generator: Generator<'a>,
-    frames: usize,
+    ids: MapIds,
     map_columns: usize,
-    maps_per_frame: usize,
 }
 
-impl Generator<'_> {
+impl<'a> Generator<'a> {
     /// Creates a new generator to output the NBT and datapack files.
-    /// It will empty the given directory if it exists and create the output directory structure
+    /// Nothing is written until `init_files()` creates the output directory structure.
     ///
     /// Run `init()` first before generating any files.
     pub fn new(
-        path: &Path,
+        path: &'a Path,
         starting_index: usize,
         top_left: Location,
         direction: Direction,
-    ) -> anyhow::Result<Generator> {
+        top: Direction,
+        version: &'static McVersion,
+        display: &'a Display,
+    ) -> anyhow::Result<Generator<'a>> {
+        if top.is_vertical() {
+            anyhow::bail!("the top of the image must point in a horizontal direction")
+        }
         if path.exists() {
             if !path.is_dir() {
                 anyhow::bail!("output path is not a directory")
@@ -52,20 +68,63 @@ impl Generator<'_> {
                 anyhow::bail!("output path is the current directory")
             }
         }
-        println!("Creating output directory structure...");
-        fs::create_dir_all(path.join("data"))?;
-        fs::create_dir_all(path.join("datapacks/mapmaker/data/mapmaker/functions"))?;
-        fs::create_dir_all(path.join("datapacks/mapmaker/data/minecraft/tags/functions"))?;
         Ok(Generator {
             path,
             starting_index,
             top_left,
             direction,
+            top,
+            version,
+            display,
         })
     }
+
+    /// Returns the path of the datapack, which is named after the display's namespace.
+    fn datapack_path(&self) -> PathBuf {
+        self.path.join("datapacks").join(self.display.namespace())
+    }
+
+    /// Returns the path of a function in the display's namespace.
+    fn function_path(&self, name: &str) -> PathBuf {
+        self.datapack_path().join(format!(
+            "data/{}/{}/{name}.mcfunction",
+            self.display.namespace(),
+            self.version.function_folder()
+        ))
+    }
+
+    /// Returns the path of a function tag in the `minecraft` namespace.
+    fn function_tag_path(&self, name: &str) -> PathBuf {
+        self.datapack_path().join(format!(
+            "data/minecraft/tags/{}/{name}.json",
+            self.version.function_folder()
+        ))
+    }
 }
 
 impl<'a> Generator<'a> {
+    /// Errors if the `.dat` file of any map to be generated already exists,
+    /// so that maps in an existing world are never overwritten.
+    /// Call it before `init_files()`, so that nothing is written to the world if it fails.
+    pub fn check_existing_maps(
+        &self,
+        frames: usize,
+        map_columns: usize,
+        map_rows: usize,
+    ) -> anyhow::Result<()> {
+        let ids = MapIds::new(self.starting_index, map_columns * map_rows, frames);
+        for map_id in ids.all() {
+            if self.path.join(format!("data/map_{map_id}.dat")).exists() {
+                anyhow::bail!(
+                    "map_{map_id}.dat already exists, refusing to overwrite maps {}..={}",
+                    ids.first(),
+                    ids.last()
+                );
+            }
+        }
+        Ok(())
+    }
+
     /// Initialize the files needed for the datapack.
     /// Consumes the generator and returns an initialized generator.
     pub fn init_files(
@@ -74,51 +133,53 @@ impl<'a> Generator<'a> {
         map_columns: usize,
         map_rows: usize,
     ) -> anyhow::Result<InitializedGenerator<'a>> {
+        println!("Creating output directory structure...");
+        fs::create_dir_all(self.path.join("data"))?;
+        fs::create_dir_all(self.function_path("").parent().unwrap())?;
+        fs::create_dir_all(self.function_tag_path("").parent().unwrap())?;
+
         // Write the pack.mcmeta file
         {
-            let mut pack_mcmeta = File::create(self.path.join("datapacks/mapmaker/pack.mcmeta"))?;
-            pack_mcmeta.write_all(PACK_MCMETA.as_bytes())?;
+            let mut pack_mcmeta = File::create(self.datapack_path().join("pack.mcmeta"))?;
+            write!(
+                &mut pack_mcmeta,
+                include_str!("datapacks/pack.mcmeta"),
+                pack_format = self.version.pack_format
+            )?;
         }
 
         // Write the loop_check.mcfunction file
         {
-            let mut loop_mcfunction = File::create(
-                self.path
-                    .join("datapacks/mapmaker/data/mapmaker/functions/loop_check.mcfunction"),
-            )?;
-            loop_mcfunction.write_all(LOOP_CHECK_MCFUNCTION.as_bytes())?;
+            let mut loop_mcfunction = File::create(self.function_path("loop_check"))?;
+            loop_mcfunction.write_all(self.display.fill(LOOP_CHECK_MCFUNCTION).as_bytes())?;
         }
 
         // Write the render.mcfunction file
         {
-            let mut render_mcfunction = File::create(
-                self.path
-                    .join("datapacks/mapmaker/data/mapmaker/functions/render.mcfunction"),
-            )?;
-            render_mcfunction.write_all(RENDER_MCFUNCTION.as_bytes())?;
+            let mut render_mcfunction = File::create(self.function_path("render"))?;
+            render_mcfunction.write_all(self.display.fill(RENDER_MCFUNCTION).as_bytes())?;
+        }
+
+        // Write the playback control functions
+        for (name, contents) in CONTROL_MCFUNCTIONS {
+            let mut control_mcfunction = File::create(self.function_path(name))?;
+            control_mcfunction.write_all(self.display.fill(contents).as_bytes())?;
         }
 
         // Write the Minecraft init and tick files
         {
-            let mut load_json = File::create(
-                self.path
-                    .join("datapacks/mapmaker/data/minecraft/tags/functions/load.json"),
-            )?;
-            load_json.write_all(LOAD_JSON.as_bytes())?;
+            let mut load_json = File::create(self.function_tag_path("load"))?;
+            load_json.write_all(self.display.fill(LOAD_JSON).as_bytes())?;
         }
         {
-            let mut tick_json = File::create(
-                self.path
-                    .join("datapacks/mapmaker/data/minecraft/tags/functions/tick.json"),
-            )?;
-            tick_json.write_all(TICK_JSON.as_bytes())?;
+            let mut tick_json = File::create(self.function_tag_path("tick"))?;
+            tick_json.write_all(self.display.fill(TICK_JSON).as_bytes())?;
         }
 
         Ok(InitializedGenerator {
+            ids: MapIds::new(self.starting_index, map_columns * map_rows, frames),
             generator: self,
-            frames,
             map_columns,
-            maps_per_frame: map_columns * map_rows,
         })
     }
 }
@@ -132,15 +193,12 @@ impl InitializedGenerator<'_> {
         index: usize,
         frame: usize,
     ) -> anyhow::Result<()> {
-        if self.frames == 0 || self.maps_per_frame == 0 {
+        if self.ids.is_empty() {
             anyhow::bail!("uninitialized generator");
         }
-        let map_index = frame * self.maps_per_frame + index;
+        let map_id = self.ids.id(frame, index);
 
-        let filename = self
-            .generator
-            .path
-            .join(format!("data/map_{map_index}.dat"));
+        let filename = self.generator.path.join(format!("data/map_{map_id}.dat"));
 
         // Used for the inner "Data" compound
         let mut data: Map<String, Value> = Map::new();
@@ -161,7 +219,7 @@ impl InitializedGenerator<'_> {
 
         // Two i64s to store the UUID (which in this case is unique but not random)
         data.insert("UUIDMost".to_string(), Value::Long(0_i64));
-        data.insert("UUIDLeast".to_string(), Value::Long(map_index as i64));
+        data.insert("UUIDLeast".to_string(), Value::Long(map_id as i64));
 
         // Add the slice of pixels to the NBT file
         data.insert("colors".to_string(), Value::from(colors));
@@ -169,7 +227,10 @@ impl InitializedGenerator<'_> {
         // Used for the root unnamed tag
         let mut nbtfile = Blob::new();
         nbtfile.insert("data", Value::Compound(data))?;
-        nbtfile.insert("DataVersion", Value::Int(NBT_DATAVERSION))?;
+        nbtfile.insert(
+            "DataVersion",
+            Value::Int(self.generator.version.data_version),
+        )?;
 
         let mut file = File::create(filename)?;
         Ok(nbtfile.to_gzip_writer(&mut file)?)
@@ -178,135 +239,357 @@ impl InitializedGenerator<'_> {
     /// Generates the `idcounts.dat` file to prevent newly opened maps in Minecraft
     /// from overwriting these generated ones.
     pub fn generate_idcounts(&self) -> anyhow::Result<()> {
-        if self.frames == 0 || self.maps_per_frame == 0 {
+        if self.ids.is_empty() {
             anyhow::bail!("uninitialized generator");
         }
 
         // Write ID Counts file to prevent new maps from overwriting the generated ones
         let mut idcounts = File::create(self.generator.path.join("data/idcounts.dat"))?;
         let mut idcounts_data: Map<String, Value> = Map::new();
-        let last_map = (self.generator.starting_index + self.frames * self.maps_per_frame) as i32;
-        idcounts_data.insert("map".to_string(), Value::Int(last_map));
+        // Minecraft stores the last map ID in use, and the next new map takes the ID after it
+        idcounts_data.insert("map".to_string(), Value::Int(self.ids.last() as i32));
         let mut idcounts_file = Blob::new();
         idcounts_file.insert("data", Value::Compound(idcounts_data))?;
 
-        idcounts_file.insert("DataVersion", Value::Int(NBT_DATAVERSION))?;
+        idcounts_file.insert(
+            "DataVersion",
+            Value::Int(self.generator.version.data_version),
+        )?;
         Ok(idcounts_file.to_gzip_writer(&mut idcounts)?)
     }
 
-    pub fn generate_datapack(&self) -> anyhow::Result<()> {
-        if self.frames == 0 || self.maps_per_frame == 0 {
+    /// Generates the datapack functions that summon the item frames and play the animation
+    /// with the given timing and playback mode.
+    pub fn generate_datapack(&self, timing: &Timing, playback: &Playback) -> anyhow::Result<()> {
+        if self.ids.is_empty() {
             anyhow::bail!("uninitialized generator");
         }
+        if let Timing::Variable(ticks) = timing {
+            if ticks.len() != self.ids.frames() {
+                anyhow::bail!("the timing does not have a duration for every frame");
+            }
+        }
 
-        self.generate_init_mcfunction()?;
-        self.generate_loop_mcfunction()?;
+        self.generate_init_mcfunction(playback)?;
+        self.generate_loop_mcfunction(playback)?;
+        self.generate_show_mcfunction()?;
+        self.generate_finish_mcfunction(playback)?;
         self.generate_restart_mcfunction()?;
+        self.generate_playback_mcfunction(timing)?;
+        self.generate_set_speed_mcfunction(timing)?;
+        self.generate_triggers_mcfunction(playback)?;
+        self.generate_frame_duration_mcfunction(timing)?;
 
         Ok(())
     }
 
-    fn generate_init_mcfunction(&self) -> anyhow::Result<()> {
-        let mut init_mcfunction = File::create(
-            self.generator
-                .path
-                .join("datapacks/mapmaker/data/mapmaker/functions/init.mcfunction"),
-        )?;
+    fn generate_init_mcfunction(&self, playback: &Playback) -> anyhow::Result<()> {
+        let mut init_mcfunction = File::create(self.generator.function_path("init"))?;
         write_header(&mut init_mcfunction)?;
         // Write the init commands, this initializes the scoreboard
         // and summons the top left map
         write!(
             &mut init_mcfunction,
             include_str!("datapacks/mapmaker/functions/templates/init_commands.in"),
-            maps_per_frame = self.maps_per_frame,
-            frames = self.frames,
-            total_maps = self.maps_per_frame * self.frames,
-            starting_index = self.generator.starting_index,
+            namespace = self.generator.display.namespace(),
+            holder = self.generator.display.holder(),
+            maps_per_frame = self.ids.maps_per_frame(),
+            frames = self.ids.frames(),
+            total_maps = self.ids.total(),
+            starting_index = self.ids.first(),
             x = self.generator.top_left.0,
             y = self.generator.top_left.1,
             z = self.generator.top_left.2,
             direction = self.generator.direction as u8,
+            rotation = self.item_rotation(),
+            item = self.generator.version.map_item(self.ids.first()),
         )?;
 
         // Summon the remaining maps
-        for i in 1..self.maps_per_frame {
-            let x: i32;
-            let y: i32;
-            let z: i32;
-            match self.generator.direction {
-                Direction::North => {
-                    x = 0 - (i % self.map_columns) as i32;
-                    y = 0 - (i / self.map_columns) as i32;
-                    z = 0;
-                }
-                Direction::South => {
-                    x = (i % self.map_columns) as i32;
-                    y = 0 - (i / self.map_columns) as i32;
-                    z = 0;
-                }
-                Direction::West => {
-                    x = 0;
-                    y = 0 - (i / self.map_columns) as i32;
-                    z = (i % self.map_columns) as i32;
-                }
-                Direction::East => {
-                    x = 0;
-                    y = 0 - (i / self.map_columns) as i32;
-                    z = 0 - (i % self.map_columns) as i32;
-                }
-            }
+        let (right, down) = self.image_axes();
+        let (right, down) = (right.vector(), down.vector());
+        for i in 1..self.ids.maps_per_frame() {
+            let column = (i % self.map_columns) as i32;
+            let row = (i / self.map_columns) as i32;
             write!(
                 &mut init_mcfunction,
                 include_str!("datapacks/mapmaker/functions/templates/init_summon.in"),
-                starting_index = self.generator.starting_index,
-                x = x,
-                y = y,
-                z = z,
+                namespace = self.generator.display.namespace(),
+                starting_index = self.ids.first(),
+                x = column * right.0 + row * down.0,
+                y = column * right.1 + row * down.1,
+                z = column * right.2 + row * down.2,
                 direction = self.generator.direction as u8,
-                i = i + self.generator.starting_index,
+                rotation = self.item_rotation(),
+                item = self.generator.version.map_item(self.ids.id(0, i)),
+                i = self.ids.id(0, i),
+            )?;
+        }
+
+        if playback.player_controls {
+            write!(
+                &mut init_mcfunction,
+                include_str!("datapacks/mapmaker/functions/templates/init_triggers.in"),
+                namespace = self.generator.display.namespace(),
             )?;
         }
         Ok(())
     }
 
-    fn generate_loop_mcfunction(&self) -> anyhow::Result<()> {
-        let mut loop_mcfunction = File::create(
-            self.generator
-                .path
-                .join("datapacks/mapmaker/data/mapmaker/functions/loop.mcfunction"),
-        )?;
+    /// Returns the coordinates of the center of the display.
+    fn center(&self) -> (f64, f64, f64) {
+        let (right, down) = self.image_axes();
+        let (right, down) = (right.vector(), down.vector());
+        let columns = (self.map_columns - 1) as f64 / 2.0;
+        let rows = (self.ids.maps_per_frame() / self.map_columns - 1) as f64 / 2.0;
+        let (x, y, z) = self.generator.top_left;
+        // Item frames sit in the middle of their block
+        (
+            x as f64 + 0.5 + columns * right.0 as f64 + rows * down.0 as f64,
+            y as f64 + 0.5 + columns * right.1 as f64 + rows * down.1 as f64,
+            z as f64 + 0.5 + columns * right.2 as f64 + rows * down.2 as f64,
+        )
+    }
+
+    /// Returns the directions in the world that the right edge and the bottom edge of the image point to.
+    fn image_axes(&self) -> (Direction, Direction) {
+        let top = self.generator.top;
+        match self.generator.direction {
+            // Viewed from above, so the image's right is clockwise from its top
+            Direction::Up => (top.clockwise(), top.opposite()),
+            // Viewed from below, which mirrors the above
+            Direction::Down => (top.counterclockwise(), top.opposite()),
+            // On a wall, the viewer faces the opposite way to the maps
+            facing => (facing.counterclockwise(), Direction::Down),
+        }
+    }
+
+    /// Returns the `ItemRotation` of the item frames, which turns the maps in 90 degree clockwise steps.
+    /// With no rotation, maps on the floor have their top to the north and maps on the ceiling to the south.
+    fn item_rotation(&self) -> u8 {
+        let top = self.generator.top;
+        match self.generator.direction {
+            Direction::Up => match top {
+                Direction::East => 1,
+                Direction::South => 2,
+                Direction::West => 3,
+                _ => 0,
+            },
+            Direction::Down => match top {
+                Direction::East => 1,
+                Direction::North => 2,
+                Direction::West => 3,
+                _ => 0,
+            },
+            _ => 0,
+        }
+    }
+
+    /// Generates the function that goes to the next frame according to the playback mode, and shows it.
+    fn generate_loop_mcfunction(&self, playback: &Playback) -> anyhow::Result<()> {
+        let mut loop_mcfunction = File::create(self.generator.function_path("loop"))?;
         write_header(&mut loop_mcfunction)?;
-        write!(
+
+        // Turning around needs at least two frames, a single frame just loops
+        if playback.mode == PlaybackMode::PingPong && self.ids.frames() > 1 {
+            write!(
+                &mut loop_mcfunction,
+                include_str!("datapacks/mapmaker/functions/templates/loop_ping_pong.in"),
+                holder = self.generator.display.holder(),
+                last_frame = self.ids.frames() - 1,
+            )?;
+        } else {
+            write!(
+                &mut loop_mcfunction,
+                include_str!("datapacks/mapmaker/functions/templates/loop_forward.in"),
+                holder = self.generator.display.holder(),
+            )?;
+        }
+        if let Some(loop_count) = playback.loop_count() {
+            write!(
+                &mut loop_mcfunction,
+                include_str!("datapacks/mapmaker/functions/templates/loop_finish.in"),
+                namespace = self.generator.display.namespace(),
+                holder = self.generator.display.holder(),
+                loop_count = loop_count,
+            )?;
+        }
+        writeln!(
             &mut loop_mcfunction,
-            include_str!("datapacks/mapmaker/functions/templates/loop_commands.in")
+            "function {}:show",
+            self.generator.display.namespace()
         )?;
+        Ok(())
+    }
 
-        for i in 0..self.maps_per_frame {
+    /// Generates the function that makes every item frame show its map of the current frame.
+    fn generate_show_mcfunction(&self) -> anyhow::Result<()> {
+        let mut show_mcfunction = File::create(self.generator.function_path("show"))?;
+        write_header(&mut show_mcfunction)?;
+        write!(
+            &mut show_mcfunction,
+            include_str!("datapacks/mapmaker/functions/templates/show_commands.in"),
+            namespace = self.generator.display.namespace(),
+            holder = self.generator.display.holder(),
+        )?;
+
+        for i in 0..self.ids.maps_per_frame() {
             write!(
-                &mut loop_mcfunction,
+                &mut show_mcfunction,
                 include_str!("datapacks/mapmaker/functions/templates/loop_scoreboard.in"),
-                i = i + self.generator.starting_index
+                namespace = self.generator.display.namespace(),
+                i = self.ids.id(0, i),
+                map_id_path = self.generator.version.map_id_path(),
             )?;
         }
         Ok(())
     }
 
-    fn generate_restart_mcfunction(&self) -> anyhow::Result<()> {
-        let mut restart_mcfunction = File::create(
-            self.generator
-                .path
-                .join("datapacks/mapmaker/data/mapmaker/functions/restart.mcfunction"),
+    /// Generates the function that stops the animation on its end frame once it has finished playing.
+    fn generate_finish_mcfunction(&self, playback: &Playback) -> anyhow::Result<()> {
+        let mut finish_mcfunction = File::create(self.generator.function_path("finish"))?;
+        write_header(&mut finish_mcfunction)?;
+        write!(
+            &mut finish_mcfunction,
+            include_str!("datapacks/mapmaker/functions/templates/finish.in"),
+            holder = self.generator.display.holder(),
+            end_frame = match playback.end_frame {
+                EndFrame::First => 0,
+                EndFrame::Last => self.ids.frames() - 1,
+            },
         )?;
+        Ok(())
+    }
+
+    fn generate_restart_mcfunction(&self) -> anyhow::Result<()> {
+        let mut restart_mcfunction = File::create(self.generator.function_path("restart"))?;
         write_header(&mut restart_mcfunction)?;
-        for i in 0..self.maps_per_frame {
+        write!(
+            &mut restart_mcfunction,
+            include_str!("datapacks/mapmaker/functions/templates/restart_commands.in"),
+            namespace = self.generator.display.namespace(),
+            holder = self.generator.display.holder(),
+        )?;
+        Ok(())
+    }
+
+    /// Generates the function that runs every tick while the animation plays, and shows the next frame when it is time.
+    fn generate_playback_mcfunction(&self, timing: &Timing) -> anyhow::Result<()> {
+        let mut playback_mcfunction = File::create(self.generator.function_path("playback"))?;
+        write_header(&mut playback_mcfunction)?;
+        match timing {
+            Timing::Constant(_) => write!(
+                &mut playback_mcfunction,
+                include_str!("datapacks/mapmaker/functions/templates/playback_constant.in"),
+                namespace = self.generator.display.namespace(),
+                holder = self.generator.display.holder(),
+            )?,
+            Timing::Variable(_) => write!(
+                &mut playback_mcfunction,
+                include_str!("datapacks/mapmaker/functions/templates/playback_variable.in"),
+                namespace = self.generator.display.namespace(),
+                holder = self.generator.display.holder(),
+            )?,
+        }
+        Ok(())
+    }
+
+    /// Generates the function that applies the playback speed in `#mapmaker speed`.
+    /// A constant frame rate is scaled by it, while variable durations count down faster or slower.
+    fn generate_set_speed_mcfunction(&self, timing: &Timing) -> anyhow::Result<()> {
+        let mut set_speed_mcfunction = File::create(self.generator.function_path("set_speed"))?;
+        write_header(&mut set_speed_mcfunction)?;
+        write!(
+            &mut set_speed_mcfunction,
+            include_str!("datapacks/mapmaker/functions/templates/set_speed.in"),
+            holder = self.generator.display.holder(),
+        )?;
+        if let Timing::Constant(rate) = timing {
             write!(
-                &mut restart_mcfunction,
-                include_str!("datapacks/mapmaker/functions/templates/restart.in"),
-                i = i + self.generator.starting_index
+                &mut set_speed_mcfunction,
+                include_str!("datapacks/mapmaker/functions/templates/set_speed_rate.in"),
+                holder = self.generator.display.holder(),
+                rate = rate,
             )?;
         }
         Ok(())
     }
+
+    /// Generates the function that runs every tick and handles the controls players have triggered.
+    /// Without player controls it does nothing.
+    fn generate_triggers_mcfunction(&self, playback: &Playback) -> anyhow::Result<()> {
+        let mut triggers_mcfunction = File::create(self.generator.function_path("triggers"))?;
+        write_header(&mut triggers_mcfunction)?;
+        if !playback.player_controls {
+            writeln!(
+                &mut triggers_mcfunction,
+                "# Player controls are turned off, see --player-controls"
+            )?;
+            return Ok(());
+        }
+
+        let players = match playback.control_radius {
+            Some(radius) => {
+                let (x, y, z) = self.center();
+                format!("positioned {x} {y} {z} as @a[distance=..{radius}]")
+            }
+            None => "as @a".to_string(),
+        };
+        write!(
+            &mut triggers_mcfunction,
+            include_str!("datapacks/mapmaker/functions/templates/triggers.in"),
+            namespace = self.generator.display.namespace(),
+            holder = self.generator.display.holder(),
+            players = players,
+        )?;
+        Ok(())
+    }
+
+    /// Generates the function that sets the duration of the current frame, in hundredths of a tick.
+    /// Only frames whose duration differs from the most common one need a command.
+    fn generate_frame_duration_mcfunction(&self, timing: &Timing) -> anyhow::Result<()> {
+        let mut frame_duration_mcfunction =
+            File::create(self.generator.function_path("frame_duration"))?;
+        write_header(&mut frame_duration_mcfunction)?;
+        let ticks = match timing {
+            Timing::Constant(_) => {
+                writeln!(
+                    &mut frame_duration_mcfunction,
+                    "# Every frame lasts as long, see playback.mcfunction"
+                )?;
+                return Ok(());
+            }
+            Timing::Variable(ticks) => ticks,
+        };
+
+        let mut counts = std::collections::HashMap::new();
+        for &duration in ticks {
+            *counts.entry(duration).or_insert(0) += 1;
+        }
+        let (&most_common, _) = counts
+            .iter()
+            .max_by_key(|&(&duration, &count)| (count, std::cmp::Reverse(duration)))
+            .unwrap();
+
+        writeln!(
+            &mut frame_duration_mcfunction,
+            "scoreboard players set {} frame_duration {}",
+            self.generator.display.holder(),
+            most_common * 100
+        )?;
+        for (frame, &duration) in ticks.iter().enumerate() {
+            if duration != most_common {
+                write!(
+                    &mut frame_duration_mcfunction,
+                    include_str!("datapacks/mapmaker/functions/templates/frame_duration.in"),
+                    holder = self.generator.display.holder(),
+                    frame = frame,
+                    duration = duration * 100,
+                )?;
+            }
+        }
+        Ok(())
+    }
 }
 
 fn write_header(file: &mut File) -> anyhow::Result<()> {
@@ -314,3 +597,99 @@ fn write_header(file: &mut File) -> anyhow::Result<()> {
     file.write_all(HEADER.as_bytes())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads the `data` compound of a gzipped NBT file.
+    fn read_data(path: &Path) -> Map<String, Value> {
+        let blob = Blob::from_gzip_reader(&mut File::open(path).unwrap()).unwrap();
+        match blob.get("data") {
+            Some(Value::Compound(data)) => data.clone(),
+            _ => panic!("{} has no data compound", path.display()),
+        }
+    }
+
+    #[test]
+    fn generated_files_agree_on_map_ids() {
+        let path = std::env::temp_dir().join(format!("mapmaker-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        let display = Display::new(None).unwrap();
+        let (frames, map_columns, map_rows) = (3, 2, 1);
+
+        let generator = Generator::new(
+            &path,
+            5,
+            (0, 64, 0),
+            Direction::North,
+            Direction::North,
+            McVersion::latest(),
+            &display,
+        )
+        .unwrap()
+        .init_files(frames, map_columns, map_rows)
+        .unwrap();
+        for frame in 0..frames {
+            for index in 0..map_columns * map_rows {
+                generator
+                    .generate_dat(&[0; 128 * 128], index, frame)
+                    .unwrap();
+            }
+        }
+        generator.generate_idcounts().unwrap();
+        generator
+            .generate_datapack(
+                &Timing::Constant(2000),
+                &Playback {
+                    mode: PlaybackMode::Loop,
+                    loop_count: None,
+                    end_frame: EndFrame::Last,
+                    player_controls: false,
+                    control_radius: None,
+                },
+            )
+            .unwrap();
+
+        // Maps 5 to 10, each with its own ID as the UUID
+        let mut dat_files = fs::read_dir(path.join("data"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.starts_with("map_"))
+            .collect::<Vec<_>>();
+        dat_files.sort();
+        let mut expected = (5..=10)
+            .map(|id| format!("map_{id}.dat"))
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(dat_files, expected);
+        for id in 5..=10 {
+            let data = read_data(&path.join(format!("data/map_{id}.dat")));
+            assert!(matches!(data.get("UUIDLeast"), Some(Value::Long(uuid)) if *uuid == id));
+        }
+
+        // idcounts.dat stores the last ID in use, not the next free one
+        let idcounts = read_data(&path.join("data/idcounts.dat"));
+        assert!(matches!(idcounts.get("map"), Some(Value::Int(10))));
+
+        // The item frames are summoned with the maps of the first frame,
+        // and shown the maps of every frame from the same starting index
+        let init = fs::read_to_string(generator.generator.function_path("init")).unwrap();
+        assert!(init.contains("\"minecraft:map_id\":5}"));
+        assert!(init.contains("\"minecraft:map_id\":6}"));
+        assert!(!init.contains("\"minecraft:map_id\":7}"));
+        assert!(init.contains("@e[tag=mapmaker.5] map_index 5"));
+        assert!(init.contains("@e[tag=mapmaker.6] map_index 6"));
+        assert!(init.contains("#mapmaker maps_per_frame 2"));
+        assert!(init.contains("#mapmaker frames 3"));
+        assert!(init.contains("#mapmaker total_maps 6"));
+        assert!(init.contains("#mapmaker starting_index 5"));
+
+        let show = fs::read_to_string(generator.generator.function_path("show")).unwrap();
+        assert!(show.contains("@e[tag=mapmaker.5] store result storage mapmaker:id id_5"));
+        assert!(show.contains("@e[tag=mapmaker.6] store result storage mapmaker:id id_6"));
+        assert!(!show.contains("id_7"));
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,48 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// How the animation plays through its frames.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlaybackMode {
+    /// Play from the first frame to the last, then start over
+    Loop,
+    /// Play from the first frame to the last, then stop
+    Once,
+    /// Play from the first frame to the last, then back to the first, and so on
+    PingPong,
+}
+
+/// The frame an animation stops on when it finishes playing.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EndFrame {
+    First,
+    Last,
+}
+
+/// How the datapack plays the animation, and when it stops.
+pub struct Playback {
+    pub mode: PlaybackMode,
+
+    /// The number of times to play the animation before stopping, forever if `None`.
+    /// For ping-pong, going forward and back again counts as once.
+    pub loop_count: Option<u32>,
+
+    pub end_frame: EndFrame,
+
+    /// Whether players can control playback with `/trigger`.
+    pub player_controls: bool,
+    /// The distance from the center of the display within which players can use the controls, any if `None`.
+    pub control_radius: Option<f64>,
+}
+
+impl Playback {
+    /// Returns the number of times the animation is played before it stops, `None` if it never stops.
+    pub fn loop_count(&self) -> Option<u32> {
+        match self.mode {
+            PlaybackMode::Once => Some(1),
+            _ => self.loop_count,
+        }
+    }
+}
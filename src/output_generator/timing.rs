@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+/// Minecraft runs 20 ticks per second, and the animation can advance at most one frame per tick.
+pub const TICKS_PER_SECOND: f64 = 20.0;
+
+/// How fast the datapack plays the animation.
+pub enum Timing {
+    /// A constant frame rate, in hundredths of a frame per second so that rates like 12.5 are exact.
+    /// Every tick adds the rate to a clock, and a frame is shown each time the clock passes
+    /// 20 frames per second, so frames alternate between lasting one and two ticks when needed.
+    Constant(u32),
+
+    /// Every frame lasts its own number of ticks, at least one.
+    Variable(Vec<u32>),
+}
+
+impl Timing {
+    /// Returns the timing for a playback frame rate if given, or else for the delays of the frames.
+    /// Frames without delays play at 20 frames per second.
+    pub fn new(fps: Option<f64>, delays: &[Option<Duration>]) -> anyhow::Result<Self> {
+        if let Some(fps) = fps {
+            if !(fps > 0.0 && fps <= TICKS_PER_SECOND) {
+                anyhow::bail!(
+                    "the playback frame rate must be above 0 and at most 20 (one frame per tick)"
+                );
+            }
+            return Ok(Timing::Constant(((fps * 100.0).round() as u32).max(1)));
+        }
+
+        let delays = match delays.iter().copied().collect::<Option<Vec<Duration>>>() {
+            Some(delays) if !delays.is_empty() => delays,
+            _ => return Ok(Timing::Constant(TICKS_PER_SECOND as u32 * 100)),
+        };
+
+        // Frames that all last as long play at a constant rate, at most one frame per tick
+        if delays.iter().all(|&delay| delay == delays[0]) {
+            if delays[0].is_zero() {
+                println!(
+                    "Warning: the frames of the input have no delay, they will play at 20 frames per second"
+                );
+                return Ok(Timing::Constant(TICKS_PER_SECOND as u32 * 100));
+            }
+            let fps = 1.0 / delays[0].as_secs_f64();
+            if fps > TICKS_PER_SECOND {
+                println!(
+                    "Warning: the input plays at {fps:.2} frames per second, which is faster than one frame per tick, it will play at 20 instead"
+                );
+            }
+            return Ok(Timing::Constant(
+                (fps.min(TICKS_PER_SECOND) * 100.0).round().max(1.0) as u32,
+            ));
+        }
+
+        // Round the time at which every frame ends to a tick, rather than every delay,
+        // so that rounding errors do not add up over the animation
+        let mut ticks = Vec::with_capacity(delays.len());
+        let (mut elapsed, mut end) = (Duration::ZERO, 0);
+        for delay in delays {
+            elapsed += delay;
+            let next_end = ((elapsed.as_secs_f64() * TICKS_PER_SECOND).round() as u32).max(end + 1);
+            ticks.push(next_end - end);
+            end = next_end;
+        }
+        Ok(Timing::Variable(ticks))
+    }
+}
@@ -0,0 +1,70 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use nbt::{Blob, Value};
+
+/// An existing Minecraft world save that the maps and datapack are installed into.
+/// The layout of a world save matches the output directory, so the world directory
+/// is used directly as the output path of the `Generator`.
+pub struct World {
+    path: PathBuf,
+
+    /// The name of the world, as shown in the world list.
+    pub name: String,
+
+    /// The `DataVersion` of the Minecraft version that last saved the world, if known.
+    pub data_version: Option<i32>,
+}
+
+impl World {
+    /// Opens the world save at the given path, reading its `level.dat` to check that it is a world.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let level_dat = path.join("level.dat");
+        let mut file = File::open(&level_dat)
+            .with_context(|| format!("{} is not a world save", path.display()))?;
+        let level = Blob::from_gzip_reader(&mut file)
+            .with_context(|| format!("failed to read {}", level_dat.display()))?;
+
+        let data = match level.get("Data") {
+            Some(Value::Compound(data)) => data,
+            _ => anyhow::bail!("{} is missing the Data tag", level_dat.display()),
+        };
+        let name = match data.get("LevelName") {
+            Some(Value::String(name)) => name.clone(),
+            _ => String::new(),
+        };
+        let data_version = match data.get("DataVersion") {
+            Some(Value::Int(data_version)) => Some(*data_version),
+            _ => None,
+        };
+
+        Ok(World {
+            path: path.to_path_buf(),
+            name,
+            data_version,
+        })
+    }
+
+    /// Returns the first map ID that is not used by the world, read from `data/idcounts.dat`.
+    /// A world without the file has no maps yet.
+    pub fn next_map_id(&self) -> anyhow::Result<usize> {
+        let idcounts_dat = self.path.join("data/idcounts.dat");
+        if !idcounts_dat.exists() {
+            return Ok(0);
+        }
+
+        let mut file = File::open(&idcounts_dat)?;
+        let idcounts = Blob::from_gzip_reader(&mut file)
+            .with_context(|| format!("failed to read {}", idcounts_dat.display()))?;
+
+        // The file stores the last map ID in use
+        match idcounts.get("data") {
+            Some(Value::Compound(data)) => match data.get("map") {
+                Some(Value::Int(last)) => Ok((*last + 1).max(0) as usize),
+                _ => Ok(0),
+            },
+            _ => anyhow::bail!("{} is missing the data tag", idcounts_dat.display()),
+        }
+    }
+}